@@ -17,9 +17,15 @@ use syn::{parse, parse_macro_input, spanned::Spanned, FnArg, ItemFn, ReturnType,
 /// If there's any user of the `oom` function then an Out-Of-Memory handler must be declared exactly
 /// once somewhere in the dependency graph
 ///
+/// The handler receives the `Layout` of the failed request, the full `AllocError` or an `OomInfo`
+/// that also describes the failed operation. It either diverges or returns an
+/// `alloc_oom::Decision`: retry the failed request or report the error to the caller. Callers that
+/// can't report errors, like `alloc_oom::oom` and `Vec::push`, panic when the handler returns
+/// `Decision::Fail`.
+///
 /// Usage
 ///
-/// ```
+/// ```ignore
 /// use core::alloc::Layout;
 ///
 /// #[oom]
@@ -27,6 +33,34 @@ use syn::{parse, parse_macro_input, spanned::Spanned, FnArg, ItemFn, ReturnType,
 ///     // ..
 /// }
 /// ```
+///
+/// ```ignore
+/// use alloc_oom::AllocError;
+///
+/// #[oom]
+/// fn oom(error: AllocError) -> ! {
+///     // ..
+/// }
+/// ```
+///
+/// ```ignore
+/// use alloc_oom::OomInfo;
+///
+/// #[oom]
+/// fn oom(info: OomInfo) -> ! {
+///     // e.g. `info.allocator()`, `info.operation()`, `info.free()`
+/// }
+/// ```
+///
+/// ```ignore
+/// use alloc_oom::{AllocError, Decision};
+///
+/// #[oom]
+/// fn oom(error: AllocError) -> Decision {
+///     // ..
+///     Decision::Fail
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn oom(args: TokenStream, input: TokenStream) -> TokenStream {
     if !args.is_empty() {
@@ -38,58 +72,100 @@ pub fn oom(args: TokenStream, input: TokenStream) -> TokenStream {
     let mut item = parse_macro_input!(input as ItemFn);
 
     let sig = &item.sig;
+    let input = if sig.inputs.len() == 1 {
+        match &sig.inputs[0] {
+            FnArg::Receiver(_) => None,
+            FnArg::Typed(arg) => match &*arg.ty {
+                Type::Path(ty) => ty.path.segments.last().and_then(|seg| {
+                    if seg.ident == "Layout" {
+                        Some(Input::Layout)
+                    } else if seg.ident == "AllocError" {
+                        Some(Input::AllocError)
+                    } else if seg.ident == "OomInfo" {
+                        Some(Input::OomInfo)
+                    } else {
+                        None
+                    }
+                }),
+                _ => None,
+            },
+        }
+    } else {
+        None
+    };
+
     let is_valid = sig.constness.is_none()
         && sig.asyncness.is_none()
         && sig.abi.is_none()
         && sig.generics.params.is_empty()
         && sig.generics.where_clause.is_none()
-        && sig.inputs.len() == 1
-        && match &sig.inputs[0] {
-            FnArg::Receiver(_) => false,
-            FnArg::Typed(arg) => match &*arg.ty {
-                Type::Path(ty) => ty
-                    .path
-                    .segments
-                    .last()
-                    .map(|seg| seg.ident == "Layout")
-                    .unwrap_or(false),
-                _ => false,
-            },
-        }
-        && is_divergent(&sig.output)
+        && input.is_some()
+        && (is_divergent(&sig.output) || returns_decision(&sig.output))
         && sig.variadic.is_none();
 
     if !is_valid {
         return parse::Error::new(
             sig.span(),
-            "function must have signature `fn(core::alloc::Layout) -> !`",
+            "function must have signature `fn(I) -> R` where `I` is one of \
+             `core::alloc::Layout`, `alloc_oom::AllocError` or `alloc_oom::OomInfo` and `R` is \
+             either `!` or `alloc_oom::Decision`",
         )
         .to_compile_error()
         .into();
     }
 
-    let attrs = mem::replace(&mut item.attrs, vec![]);
+    let attrs = mem::take(&mut item.attrs);
     let vis = &item.vis;
     let ident = &sig.ident;
+    let arg = match input {
+        Some(Input::Layout) => quote!(info.layout()),
+        Some(Input::AllocError) => quote!(info.error()),
+        _ => quote!(info),
+    };
+    // a diverging handler ends the program's normal flow so the condition is recorded beforehand
+    let fatal = if is_divergent(&sig.output) {
+        quote!(alloc_oom::fatal(&info);)
+    } else {
+        quote!()
+    };
     quote!(
         #(#attrs)*
         #[export_name = "oom"]
-        #vis fn #ident(layout: core::alloc::Layout) {
+        #vis fn #ident(info: alloc_oom::OomInfo) -> alloc_oom::Decision {
             #[inline(always)]
             #item
 
-            #ident(layout)
+            #fatal
+            #ident(#arg)
         }
     )
     .into()
 }
 
-fn is_divergent(rt: &ReturnType) -> bool {
+enum Input {
+    Layout,
+    AllocError,
+    OomInfo,
+}
+
+fn returns_decision(rt: &ReturnType) -> bool {
     match rt {
         ReturnType::Default => false,
-        ReturnType::Type(_, ty) => match **ty {
-            Type::Never(_) => true,
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(ty) => ty
+                .path
+                .segments
+                .last()
+                .map(|seg| seg.ident == "Decision")
+                .unwrap_or(false),
             _ => false,
         },
     }
 }
+
+fn is_divergent(rt: &ReturnType) -> bool {
+    match rt {
+        ReturnType::Default => false,
+        ReturnType::Type(_, ty) => matches!(**ty, Type::Never(_)),
+    }
+}
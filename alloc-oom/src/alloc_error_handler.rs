@@ -0,0 +1,15 @@
+use core::alloc::Layout;
+
+use crate::{AllocError, OomInfo, Operation};
+
+/// Forwards the allocation errors of the `alloc` crate to the `#[oom]` handler
+///
+/// `alloc` can't retry a failed request nor report the error so returning a `Decision` from the
+/// handler results in a panic
+#[alloc_error_handler]
+fn alloc_error_handler(layout: Layout) -> ! {
+    crate::oom(OomInfo::new(
+        AllocError::exhausted(layout),
+        Operation::Alloc,
+    ))
+}
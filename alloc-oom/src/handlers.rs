@@ -0,0 +1,75 @@
+//! Ready-made Out-Of-Memory handlers, selected with Cargo features
+
+#[cfg(any(
+    all(feature = "panic", feature = "abort"),
+    all(feature = "panic", feature = "spin"),
+    all(feature = "panic", feature = "reset"),
+    all(feature = "abort", feature = "spin"),
+    all(feature = "abort", feature = "reset"),
+    all(feature = "spin", feature = "reset"),
+))]
+compile_error!("at most one of the `panic`, `abort`, `spin` and `reset` features can be enabled");
+
+/// Panics with a message that includes the layout of the failed request
+#[cfg(feature = "panic")]
+#[export_name = "oom"]
+fn panic(info: crate::OomInfo) -> crate::Decision {
+    crate::failed(info)
+}
+
+/// Executes a permanently undefined instruction
+#[cfg(feature = "abort")]
+#[export_name = "oom"]
+fn abort(info: crate::OomInfo) -> crate::Decision {
+    crate::fatal(&info);
+
+    unsafe {
+        #[cfg(target_arch = "arm")]
+        core::arch::asm!("udf #0", options(noreturn));
+
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        core::arch::asm!("ud2", options(noreturn));
+    }
+
+    #[cfg(not(any(target_arch = "arm", target_arch = "x86", target_arch = "x86_64")))]
+    loop {
+        core::hint::spin_loop()
+    }
+}
+
+/// Spins forever
+#[cfg(feature = "spin")]
+#[export_name = "oom"]
+fn spin(info: crate::OomInfo) -> crate::Decision {
+    crate::fatal(&info);
+
+    loop {
+        core::hint::spin_loop()
+    }
+}
+
+/// Calls the `oom_reset` function provided by the application
+#[cfg(feature = "reset")]
+#[export_name = "oom"]
+fn reset(info: crate::OomInfo) -> crate::Decision {
+    extern "Rust" {
+        fn oom_reset(info: crate::OomInfo) -> !;
+    }
+
+    crate::fatal(&info);
+
+    unsafe { oom_reset(info) }
+}
+
+/// Used when the application declares no `#[oom]` handler
+#[cfg(feature = "weak-default")]
+#[export_name = "oom"]
+#[linkage = "weak"]
+fn weak_default(info: crate::OomInfo) -> crate::Decision {
+    crate::fatal(&info);
+
+    panic!(
+        "no Out-Of-Memory handler has been declared (see `alloc_oom::oom`); {}",
+        info
+    )
+}
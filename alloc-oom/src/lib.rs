@@ -1,21 +1,131 @@
 //! Stable alternative to `#[alloc_error_handler]`
+//!
+//! The Out-Of-Memory handler, declared with the [`#[oom]`](oom) attribute, either diverges or
+//! returns a [`Decision`]: retry the failed request or report the error to the caller. Fallible
+//! callers (e.g. `Vec::try_reserve`) get the error; infallible ones panic.
+//!
+//! The handler can receive an [`OomInfo`] that tells which allocator failed, what operation was
+//! being performed and, if the allocator reports them, how many bytes were free and in use.
+//!
+//! Before giving up on a failed request, allocation paths can run the reclaim hooks registered with
+//! [`register_reclaim`] to free memory (e.g. drop caches) and then retry the request; see
+//! [`retry`] and [`try_retry`]. The Out-Of-Memory handler is only called when the hooks free
+//! nothing.
+//!
+//! # Cargo features
+//!
+//! Instead of declaring an `#[oom]` handler, applications can enable one of these features to use
+//! a ready-made handler:
+//!
+//! - `panic`: panics with a message that describes the failed request, including its layout
+//! - `abort`: executes a permanently undefined instruction (`udf` on ARM) to stop the program
+//! - `spin`: spins forever
+//! - `reset`: calls `oom_reset`, a function the application provides, e.g. to reset the device
+//!
+//! ```ignore
+//! use alloc_oom::OomInfo;
+//! use cortex_m::peripheral::SCB;
+//!
+//! #[export_name = "oom_reset"]
+//! fn oom_reset(_info: OomInfo) -> ! {
+//!     SCB::sys_reset()
+//! }
+//! ```
+//!
+//! - `weak-default` (nightly only): declares a weak default handler so a missing handler results in
+//!   a panic that explains the problem rather than in an undefined symbol link error
+//! - `alloc-error-handler` (nightly only): installs an `#[alloc_error_handler]` that forwards the
+//!   allocation errors of the `alloc` crate (e.g. of `alloc::vec::Vec` on the `#[global_allocator]`)
+//!   to the `#[oom]` handler, so one handler covers all the allocators. Stable Rust has no hook to
+//!   override `alloc`'s default handler, which panics. This feature can only be used in `no_std`
+//!   programs: `std` provides its own handler.
+//! - `record`: writes every fatal Out-Of-Memory condition into a `.uninit` RAM section so it can be
+//!   read back after a reset; see the [`record`] module
 
+#![cfg_attr(feature = "alloc-error-handler", feature(alloc_error_handler))]
+#![cfg_attr(feature = "weak-default", feature(linkage))]
 #![deny(missing_docs)]
 #![deny(warnings)]
 #![no_std]
 
-use core::alloc::Layout;
-
 pub use alloc_oom_macros::oom;
+pub use alloc_trait::{AllocError, AllocErrorKind, Decision, OomHandler, OomInfo, Operation};
+
+pub use crate::reclaim::{
+    reclaim, register_reclaim, retry, try_retry, Reclaim, MAX_RECLAIM_HOOKS, MAX_RECLAIM_ROUNDS,
+};
+
+#[cfg(feature = "alloc-error-handler")]
+mod alloc_error_handler;
+mod handlers;
+mod reclaim;
+pub mod record;
 
-/// Calls the Out-Of-Memory handler
+/// Calls the Out-Of-Memory handler and returns its decision
 ///
-/// If there's any user of the `oom` function then an Out-Of-Memory handler must be declared (using
-/// the `#[oom]` attribute) exactly once somewhere in the dependency graph
-pub fn oom(layout: Layout) -> ! {
+/// If the allocator that failed has a dedicated handler (see `cortex_m_tm_alloc::allocator`) that
+/// handler is called; otherwise the global one is.
+///
+/// If there's any user of the `handle` or `oom` functions then a global Out-Of-Memory handler must
+/// be declared (using the `#[oom]` attribute) exactly once somewhere in the dependency graph
+pub fn handle(info: impl Into<OomInfo>) -> Decision {
     extern "Rust" {
-        fn oom(layout: Layout) -> !;
+        fn oom(info: OomInfo) -> Decision;
+    }
+
+    let info = info.into();
+    match info.error().handler() {
+        Some(handler) => handler(info),
+        None => unsafe { oom(info) },
+    }
+}
+
+/// Calls the Out-Of-Memory handler and never returns
+///
+/// This is meant to be used by callers that can't report errors nor retry the request. If the
+/// handler returns a [`Decision`], rather than diverge, this function panics.
+pub fn oom(info: impl Into<OomInfo>) -> ! {
+    let info = info.into();
+    handle(info);
+
+    failed(info)
+}
+
+/// Panics with a message that describes the failed request
+pub(crate) fn failed(info: OomInfo) -> ! {
+    fatal(&info);
+
+    panic!("memory allocation failed: {}", info)
+}
+
+/// IMPLEMENTATION DETAIL: calls an Out-Of-Memory handler dedicated to an allocator
+///
+/// Implemented for both kinds of handlers so a diverging one records the condition before it runs
+#[doc(hidden)]
+pub trait DedicatedHandler {
+    /// Calls the handler
+    fn call(self, info: OomInfo) -> Decision;
+}
+
+impl DedicatedHandler for fn(OomInfo) -> Decision {
+    fn call(self, info: OomInfo) -> Decision {
+        self(info)
+    }
+}
+
+impl DedicatedHandler for fn(OomInfo) -> ! {
+    fn call(self, info: OomInfo) -> Decision {
+        fatal(&info);
+
+        self(info)
     }
+}
 
-    unsafe { oom(layout) }
+/// IMPLEMENTATION DETAIL: called right before a diverging Out-Of-Memory handler runs
+#[doc(hidden)]
+pub fn fatal(info: &OomInfo) {
+    #[cfg(feature = "record")]
+    record::write(info);
+    #[cfg(not(feature = "record"))]
+    let _ = info;
 }
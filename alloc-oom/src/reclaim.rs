@@ -0,0 +1,130 @@
+use core::{
+    mem,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc_trait::AllocError;
+
+use crate::{Decision, OomInfo};
+
+/// A reclaim hook
+///
+/// The hook receives the error of the failed request and tries to free memory, e.g. by dropping a
+/// cache; it must return `true` if it freed any memory and `false` otherwise
+pub type Reclaim = fn(&AllocError) -> bool;
+
+/// Maximum number of reclaim hooks that can be registered
+pub const MAX_RECLAIM_HOOKS: usize = 4;
+
+// `Reclaim` function pointers stored as `usize`; `0` means "empty slot"
+static HOOKS: [AtomicUsize; MAX_RECLAIM_HOOKS] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+/// Registers a reclaim hook
+///
+/// Hooks run in registration order. Returns the `hook` back if `MAX_RECLAIM_HOOKS` hooks have
+/// already been registered.
+///
+/// **NOTE** on targets without compare-and-swap instructions (e.g. ARMv6-M) registration is not
+/// synchronized: register the hooks from a single context, e.g. during initialization
+pub fn register_reclaim(hook: Reclaim) -> Result<(), Reclaim> {
+    for slot in &HOOKS {
+        if claim(slot, hook as usize) {
+            return Ok(());
+        }
+    }
+
+    Err(hook)
+}
+
+/// Stores `hook` in `slot` if the slot is empty
+#[cfg(target_has_atomic = "ptr")]
+fn claim(slot: &AtomicUsize, hook: usize) -> bool {
+    slot.compare_exchange(0, hook, Ordering::Release, Ordering::Relaxed)
+        .is_ok()
+}
+
+/// Stores `hook` in `slot` if the slot is empty
+#[cfg(not(target_has_atomic = "ptr"))]
+fn claim(slot: &AtomicUsize, hook: usize) -> bool {
+    if slot.load(Ordering::Acquire) == 0 {
+        slot.store(hook, Ordering::Release);
+        true
+    } else {
+        false
+    }
+}
+
+/// Runs all the registered reclaim hooks
+///
+/// Returns `true` if any of them freed memory
+pub fn reclaim(error: &AllocError) -> bool {
+    let mut freed = false;
+    for slot in &HOOKS {
+        let raw = slot.load(Ordering::Acquire);
+        if raw == 0 {
+            break;
+        }
+
+        let hook = unsafe { mem::transmute::<usize, Reclaim>(raw) };
+        freed |= hook(error);
+    }
+
+    freed
+}
+
+/// Maximum number of times [`retry`] and [`try_retry`] run the reclaim hooks for a single request
+pub const MAX_RECLAIM_ROUNDS: usize = 8;
+
+/// Runs `f` until it succeeds
+///
+/// When `f` fails the reclaim hooks are run and `f` is retried; if the hooks free no memory the
+/// Out-Of-Memory handler decides what to do. If the handler decides to report the error this
+/// function panics.
+///
+/// See [`try_retry`] for when this function gives up on the reclaim hooks
+pub fn retry<T, E>(f: impl FnMut() -> Result<T, E>) -> T
+where
+    E: Into<OomInfo>,
+{
+    run(f).unwrap_or_else(|info| crate::failed(info))
+}
+
+/// Runs `f` until it succeeds or the Out-Of-Memory handler decides to report the error
+///
+/// When `f` fails the reclaim hooks are run and `f` is retried; if the hooks free no memory the
+/// Out-Of-Memory handler decides what to do. Once the hooks have run `MAX_RECLAIM_ROUNDS` times
+/// the handler is called right away, so hooks that claim to free memory without freeing enough
+/// can't make this function loop forever; a handler that always returns `Decision::Retry` does
+pub fn try_retry<T, E>(f: impl FnMut() -> Result<T, E>) -> Result<T, AllocError>
+where
+    E: Into<OomInfo>,
+{
+    run(f).map_err(|info| info.error())
+}
+
+fn run<T, E>(mut f: impl FnMut() -> Result<T, E>) -> Result<T, OomInfo>
+where
+    E: Into<OomInfo>,
+{
+    let mut rounds = 0;
+    loop {
+        match f() {
+            Ok(x) => return Ok(x),
+            Err(e) => {
+                let info = e.into();
+                rounds += 1;
+                if rounds > MAX_RECLAIM_ROUNDS || !reclaim(&info.error()) {
+                    match crate::handle(info) {
+                        Decision::Retry => {}
+                        Decision::Fail => return Err(info),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,456 @@
+//! Post-mortem Out-Of-Memory records
+//!
+//! With the `record` feature enabled every fatal Out-Of-Memory condition is written into a
+//! [`RecordSlot`] placed in the `.uninit` RAM section. A condition is fatal when it ends in a panic
+//! (see [`oom`](crate::oom) and [`retry`](crate::retry)) or in a diverging handler, e.g. one that
+//! resets the device; conditions that are retried, or reported to the caller of
+//! [`try_retry`](crate::try_retry), are not recorded. The slot is not initialized at boot so the
+//! record survives a (watchdog) reset; after the reset read it back with [`last`] and then
+//! [`clear`] it.
+//!
+//! A handler that returns a [`Decision`](crate::Decision) also results in a record when the caller
+//! can't report the error and panics instead, e.g. when the handler returns `Decision::Fail` to
+//! [`oom`](crate::oom). Diverging handlers, whether declared with `#[oom]`, selected with a Cargo
+//! feature or dedicated to an allocator (see `cortex_m_tm_alloc::allocator`), write the record
+//! before they run.
+//!
+//! [`RecordSlot`] can also be used on its own, on any buffer of [`SLOT_SIZE`] bytes, e.g. on the
+//! host:
+//!
+//! ```
+//! use core::alloc::Layout;
+//!
+//! use alloc_oom::{
+//!     record::{RecordSlot, SLOT_SIZE},
+//!     AllocError, OomInfo, Operation,
+//! };
+//!
+//! let mut buffer = [0; SLOT_SIZE];
+//! let mut slot = RecordSlot::new(&mut buffer);
+//!
+//! let error = AllocError::exhausted(Layout::new::<[u8; 32]>()).with_allocator("A");
+//! slot.write(&OomInfo::new(error, Operation::Alloc));
+//!
+//! let record = slot.read().unwrap();
+//! assert_eq!(record.sequence(), 1);
+//! assert_eq!(record.layout().size(), 32);
+//! assert_eq!(record.allocator(), Some("A"));
+//!
+//! slot.clear();
+//! assert!(slot.read().is_none());
+//! ```
+
+use core::{alloc::Layout, convert::TryFrom, str};
+
+use alloc_trait::{OomInfo, Operation, Usage};
+
+/// Maximum length, in bytes, of the allocator name kept in a record; longer names are truncated
+pub const NAME_LEN: usize = 16;
+
+/// Size, in bytes, of the memory used by a [`RecordSlot`]
+pub const SLOT_SIZE: usize = offset::CHECKSUM + 4;
+
+/// The slot holds a record
+const RECORD: u32 = 0x4f4f_4d21;
+/// The slot holds no record but its sequence number is valid
+const CLEARED: u32 = 0x4f4f_4d2e;
+
+// Position of the fields in the slot; all of them are stored in little endian order
+mod offset {
+    pub const MAGIC: usize = 0;
+    pub const SEQUENCE: usize = 4;
+    pub const OPERATION: usize = 8;
+    pub const HAS_USAGE: usize = 12;
+    pub const NAME_LEN: usize = 16;
+    pub const SIZE: usize = 20;
+    pub const ALIGN: usize = 28;
+    pub const FREE: usize = 36;
+    pub const USED: usize = 44;
+    pub const LARGEST_FREE_BLOCK: usize = 52;
+    pub const NAME: usize = 60;
+    pub const CHECKSUM: usize = NAME + super::NAME_LEN;
+}
+
+/// An Out-Of-Memory condition, as recorded in a [`RecordSlot`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OomRecord {
+    sequence: u32,
+    layout: Layout,
+    operation: Operation,
+    name: [u8; NAME_LEN],
+    name_len: usize,
+    usage: Option<Usage>,
+}
+
+impl OomRecord {
+    /// Number of Out-Of-Memory conditions recorded in the slot so far, this one included; it
+    /// starts at 1 and is not reset by [`RecordSlot::clear`]
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// The layout of the request that failed
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// The operation that failed
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// The name of the allocator that failed, if known; possibly truncated to [`NAME_LEN`] bytes
+    pub fn allocator(&self) -> Option<&str> {
+        if self.name_len == 0 {
+            None
+        } else {
+            str::from_utf8(&self.name[..self.name_len]).ok()
+        }
+    }
+
+    /// The allocator's usage figures, if the allocator reported them
+    pub fn usage(&self) -> Option<Usage> {
+        self.usage
+    }
+}
+
+/// A buffer that holds an [`OomRecord`]
+///
+/// The contents are checksummed and validated before a record is built from them: garbage, e.g.
+/// the contents of RAM after a power-on reset, reads back as "no record"
+pub struct RecordSlot<'a> {
+    bytes: &'a mut [u8],
+}
+
+impl<'a> RecordSlot<'a> {
+    /// Uses the first [`SLOT_SIZE`] bytes of `bytes` as a slot
+    ///
+    /// The contents are left as they are: if they hold a record [`read`](RecordSlot::read) returns
+    /// it
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `bytes` is shorter than [`SLOT_SIZE`]
+    pub fn new(bytes: &'a mut [u8]) -> Self {
+        assert!(bytes.len() >= SLOT_SIZE, "record slot too small");
+
+        Self {
+            bytes: &mut bytes[..SLOT_SIZE],
+        }
+    }
+
+    /// Records `info`, overwriting the record the slot may hold
+    pub fn write(&mut self, info: &OomInfo) {
+        let sequence = if self.is_valid() {
+            self.u32_at(offset::SEQUENCE).wrapping_add(1)
+        } else {
+            1
+        };
+
+        let layout = info.layout();
+        let usage = info.error().usage();
+        let name = info.allocator().unwrap_or("");
+        // truncate at a `char` boundary
+        let mut name_len = name.len().min(NAME_LEN);
+        while !name.is_char_boundary(name_len) {
+            name_len -= 1;
+        }
+
+        self.set_u32(offset::MAGIC, RECORD);
+        self.set_u32(offset::SEQUENCE, sequence);
+        self.set_u32(
+            offset::OPERATION,
+            match info.operation() {
+                Operation::Alloc => 0,
+                Operation::Realloc => 1,
+                Operation::QueueFull => 2,
+            },
+        );
+        self.set_u32(offset::HAS_USAGE, usage.is_some() as u32);
+        self.set_u32(offset::NAME_LEN, name_len as u32);
+        self.set_u64(offset::SIZE, layout.size() as u64);
+        self.set_u64(offset::ALIGN, layout.align() as u64);
+        self.set_u64(offset::FREE, usage.map(|u| u.free as u64).unwrap_or(0));
+        self.set_u64(offset::USED, usage.map(|u| u.used as u64).unwrap_or(0));
+        self.set_u64(
+            offset::LARGEST_FREE_BLOCK,
+            usage.map(|u| u.largest_free_block as u64).unwrap_or(0),
+        );
+        let field = &mut self.bytes[offset::NAME..offset::NAME + NAME_LEN];
+        field.iter_mut().for_each(|byte| *byte = 0);
+        field[..name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+        self.set_u32(offset::CHECKSUM, self.checksum());
+    }
+
+    /// Reads back the record, if the slot holds one
+    pub fn read(&self) -> Option<OomRecord> {
+        if !self.is_valid() || self.u32_at(offset::MAGIC) != RECORD {
+            return None;
+        }
+
+        let layout = Layout::from_size_align(
+            usize::try_from(self.u64_at(offset::SIZE)).ok()?,
+            usize::try_from(self.u64_at(offset::ALIGN)).ok()?,
+        )
+        .ok()?;
+        let operation = match self.u32_at(offset::OPERATION) {
+            0 => Operation::Alloc,
+            1 => Operation::Realloc,
+            2 => Operation::QueueFull,
+            _ => return None,
+        };
+        let name_len = self.u32_at(offset::NAME_LEN) as usize;
+        if name_len > NAME_LEN {
+            return None;
+        }
+        let mut name = [0; NAME_LEN];
+        name.copy_from_slice(&self.bytes[offset::NAME..offset::NAME + NAME_LEN]);
+        str::from_utf8(&name[..name_len]).ok()?;
+        let usage = match self.u32_at(offset::HAS_USAGE) {
+            0 => None,
+            1 => Some(Usage {
+                free: usize::try_from(self.u64_at(offset::FREE)).ok()?,
+                used: usize::try_from(self.u64_at(offset::USED)).ok()?,
+                largest_free_block: usize::try_from(self.u64_at(offset::LARGEST_FREE_BLOCK))
+                    .ok()?,
+            }),
+            _ => return None,
+        };
+
+        Some(OomRecord {
+            sequence: self.u32_at(offset::SEQUENCE),
+            layout,
+            operation,
+            name,
+            name_len,
+            usage,
+        })
+    }
+
+    /// Discards the record; the sequence number is preserved
+    pub fn clear(&mut self) {
+        let sequence = if self.is_valid() {
+            self.u32_at(offset::SEQUENCE)
+        } else {
+            0
+        };
+
+        self.bytes.iter_mut().for_each(|byte| *byte = 0);
+        self.set_u32(offset::MAGIC, CLEARED);
+        self.set_u32(offset::SEQUENCE, sequence);
+        self.set_u32(offset::CHECKSUM, self.checksum());
+    }
+
+    fn is_valid(&self) -> bool {
+        let magic = self.u32_at(offset::MAGIC);
+
+        (magic == RECORD || magic == CLEARED) && self.u32_at(offset::CHECKSUM) == self.checksum()
+    }
+
+    // FNV-1a over all the bytes but the checksum
+    fn checksum(&self) -> u32 {
+        self.bytes[..offset::CHECKSUM]
+            .iter()
+            .fold(0x811c_9dc5, |hash, byte| {
+                (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+            })
+    }
+
+    fn u32_at(&self, at: usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.bytes[at..at + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn u64_at(&self, at: usize) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&self.bytes[at..at + 8]);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn set_u32(&mut self, at: usize, value: u32) {
+        self.bytes[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_u64(&mut self, at: usize, value: u64) {
+        self.bytes[at..at + 8].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[cfg(feature = "record")]
+mod uninit {
+    use core::{cell::UnsafeCell, mem::MaybeUninit, ptr};
+
+    use super::{OomRecord, RecordSlot, SLOT_SIZE};
+    use crate::OomInfo;
+
+    struct Slot(UnsafeCell<MaybeUninit<[u8; SLOT_SIZE]>>);
+
+    // NOTE the slot is only accessed through raw pointers and volatile operations: its contents
+    // are unknown to the compiler after a reset
+    unsafe impl Sync for Slot {}
+
+    #[link_section = ".uninit.alloc_oom.RECORD"]
+    static SLOT: Slot = Slot(UnsafeCell::new(MaybeUninit::uninit()));
+
+    /// Copies the slot out of RAM
+    fn load() -> [u8; SLOT_SIZE] {
+        let slot = SLOT.0.get().cast::<u8>();
+
+        // NOTE after a power-on reset the slot holds whatever the RAM powered up with. It's read
+        // as plain bytes, which have no invalid values, and `RecordSlot` validates them before it
+        // builds a record from them
+        let mut bytes = [0; SLOT_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = unsafe { ptr::read_volatile(slot.add(i)) };
+        }
+        bytes
+    }
+
+    fn store(bytes: &[u8; SLOT_SIZE]) {
+        let slot = SLOT.0.get().cast::<u8>();
+
+        for (i, byte) in bytes.iter().enumerate() {
+            unsafe { ptr::write_volatile(slot.add(i), *byte) }
+        }
+    }
+
+    pub(crate) fn write(info: &OomInfo) {
+        let mut bytes = load();
+        RecordSlot::new(&mut bytes).write(info);
+        store(&bytes);
+    }
+
+    /// Returns the Out-Of-Memory record left by a previous run of the program, if any
+    pub fn last() -> Option<OomRecord> {
+        RecordSlot::new(&mut load()).read()
+    }
+
+    /// Discards the Out-Of-Memory record
+    pub fn clear() {
+        let mut bytes = load();
+        RecordSlot::new(&mut bytes).clear();
+        store(&bytes);
+    }
+}
+
+#[cfg(feature = "record")]
+pub(crate) use uninit::write;
+#[cfg(feature = "record")]
+pub use uninit::{clear, last};
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::Layout;
+
+    use alloc_trait::{AllocError, OomInfo, Operation, Usage};
+
+    use super::{offset, RecordSlot, NAME_LEN, SLOT_SIZE};
+
+    fn info(name: &'static str) -> OomInfo {
+        let error = AllocError::exhausted(Layout::from_size_align(24, 8).unwrap())
+            .with_allocator(name)
+            .with_usage(Usage {
+                free: 16,
+                used: 112,
+                largest_free_block: 8,
+            });
+
+        OomInfo::new(error, Operation::Realloc)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut bytes = [0; SLOT_SIZE];
+        let mut slot = RecordSlot::new(&mut bytes);
+        slot.write(&info("A"));
+
+        let record = slot.read().unwrap();
+        assert_eq!(record.sequence(), 1);
+        assert_eq!(record.layout(), Layout::from_size_align(24, 8).unwrap());
+        assert_eq!(record.operation(), Operation::Realloc);
+        assert_eq!(record.allocator(), Some("A"));
+        assert_eq!(
+            record.usage(),
+            Some(Usage {
+                free: 16,
+                used: 112,
+                largest_free_block: 8,
+            })
+        );
+    }
+
+    #[test]
+    fn garbage() {
+        let mut bytes = [0; SLOT_SIZE];
+        assert!(RecordSlot::new(&mut bytes).read().is_none());
+
+        // xorshift
+        let mut state = 0x2545_f491_u32;
+        for byte in bytes.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        let mut slot = RecordSlot::new(&mut bytes);
+        assert!(slot.read().is_none());
+
+        // garbage doesn't count as a previous record
+        slot.write(&info("A"));
+        assert_eq!(slot.read().unwrap().sequence(), 1);
+    }
+
+    #[test]
+    fn checksum() {
+        let mut bytes = [0; SLOT_SIZE];
+        RecordSlot::new(&mut bytes).write(&info("A"));
+
+        for at in &[
+            offset::SEQUENCE,
+            offset::SIZE,
+            offset::NAME,
+            offset::CHECKSUM,
+        ] {
+            let mut corrupted = bytes;
+            corrupted[*at] ^= 1;
+            assert!(RecordSlot::new(&mut corrupted).read().is_none());
+        }
+    }
+
+    #[test]
+    fn clear() {
+        let mut bytes = [0; SLOT_SIZE];
+        let mut slot = RecordSlot::new(&mut bytes);
+        slot.write(&info("A"));
+        slot.write(&info("A"));
+        assert_eq!(slot.read().unwrap().sequence(), 2);
+
+        slot.clear();
+        assert!(slot.read().is_none());
+
+        slot.write(&info("A"));
+        assert_eq!(slot.read().unwrap().sequence(), 3);
+    }
+
+    #[test]
+    fn truncation() {
+        let mut bytes = [0; SLOT_SIZE];
+        let mut slot = RecordSlot::new(&mut bytes);
+
+        slot.write(&info("ABCDEFGHIJKLMNOPQRSTUVWXYZ"));
+        assert_eq!(slot.read().unwrap().allocator(), Some("ABCDEFGHIJKLMNOP"));
+
+        // `€` is 3 bytes long; the 6th one would end past `NAME_LEN`
+        slot.write(&info("€€€€€€"));
+        let record = slot.read().unwrap();
+        assert_eq!(record.allocator(), Some("€€€€€"));
+        assert!(record.allocator().unwrap().len() < NAME_LEN);
+    }
+
+    #[test]
+    #[should_panic(expected = "record slot too small")]
+    fn too_small() {
+        RecordSlot::new(&mut [0; SLOT_SIZE - 1]);
+    }
+}
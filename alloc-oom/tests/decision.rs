@@ -0,0 +1,95 @@
+use std::{
+    alloc::Layout,
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use alloc_oom::{AllocError, Decision};
+
+// NOTE no reclaim hook is registered so every failed attempt reaches the handler. The handler state
+// is process-wide so the tests run one at a time
+static LOCK: Mutex<()> = Mutex::new(());
+/// Number of `Decision::Retry` the handler returns before returning `Decision::Fail`
+static RETRIES: AtomicUsize = AtomicUsize::new(0);
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[alloc_oom::oom]
+fn on_oom(_error: AllocError) -> Decision {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+
+    let retries = RETRIES.load(Ordering::Relaxed);
+    if retries == 0 {
+        Decision::Fail
+    } else {
+        RETRIES.store(retries - 1, Ordering::Relaxed);
+        Decision::Retry
+    }
+}
+
+fn setup(retries: usize) -> MutexGuard<'static, ()> {
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    RETRIES.store(retries, Ordering::Relaxed);
+    CALLS.store(0, Ordering::Relaxed);
+    guard
+}
+
+fn error() -> AllocError {
+    AllocError::exhausted(Layout::new::<u64>())
+}
+
+/// Fails the first `n` attempts
+fn fail_first(n: usize) -> impl FnMut() -> Result<usize, AllocError> {
+    let mut attempts = 0;
+    move || {
+        attempts += 1;
+        if attempts <= n {
+            Err(error())
+        } else {
+            Ok(attempts)
+        }
+    }
+}
+
+#[test]
+fn retry() {
+    let _guard = setup(2);
+
+    assert_eq!(alloc_oom::try_retry(fail_first(2)), Ok(3));
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+
+    assert_eq!(alloc_oom::retry(fail_first(0)), 1);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn fail() {
+    let _guard = setup(1);
+
+    assert_eq!(alloc_oom::try_retry(fail_first(5)), Err(error()));
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn fail_infallible() {
+    let _guard = setup(0);
+
+    let payload = panic::catch_unwind(|| alloc_oom::retry(fail_first(1))).unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(
+        message.starts_with("memory allocation failed"),
+        "{}",
+        message
+    );
+
+    let payload = panic::catch_unwind(|| alloc_oom::oom(error())).unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(
+        message.starts_with("memory allocation failed"),
+        "{}",
+        message
+    );
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+}
@@ -0,0 +1,18 @@
+//! The handler of the `panic` feature
+
+use std::{alloc::Layout, panic};
+
+use alloc_oom::{AllocError, OomInfo, Operation};
+
+#[test]
+fn panic() {
+    let error = AllocError::exhausted(Layout::from_size_align(24, 8).unwrap()).with_allocator("A");
+
+    let payload = panic::catch_unwind(|| alloc_oom::oom(OomInfo::new(error, Operation::Realloc)))
+        .unwrap_err();
+
+    assert_eq!(
+        payload.downcast_ref::<String>().unwrap(),
+        "memory allocation failed: realloc: memory exhausted in `A` (size: 24, align: 8)"
+    );
+}
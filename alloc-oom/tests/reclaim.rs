@@ -0,0 +1,119 @@
+use std::{
+    alloc::Layout,
+    panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard, Once,
+    },
+};
+
+use alloc_oom::{AllocError, Reclaim, MAX_RECLAIM_HOOKS, MAX_RECLAIM_ROUNDS};
+
+#[alloc_oom::oom]
+fn on_oom(error: AllocError) -> ! {
+    panic!("out of memory: {}", error)
+}
+
+// NOTE the hooks are process-wide so the tests run one at a time
+static LOCK: Mutex<()> = Mutex::new(());
+static CALLS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static FREE: AtomicBool = AtomicBool::new(false);
+
+fn hook<const N: usize>(_: &AllocError) -> bool {
+    CALLS.lock().unwrap().push(N);
+    FREE.load(Ordering::Relaxed)
+}
+
+const HOOKS: [Reclaim; MAX_RECLAIM_HOOKS] = [hook::<0>, hook::<1>, hook::<2>, hook::<3>];
+
+/// Registers all the hooks, once
+fn setup(free: bool) -> MutexGuard<'static, ()> {
+    static ONCE: Once = Once::new();
+
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    ONCE.call_once(|| {
+        for hook in HOOKS.iter() {
+            alloc_oom::register_reclaim(*hook).unwrap();
+        }
+    });
+    FREE.store(free, Ordering::Relaxed);
+    calls();
+    guard
+}
+
+/// Drains the list of hook calls
+fn calls() -> Vec<usize> {
+    CALLS.lock().unwrap().drain(..).collect()
+}
+
+fn error() -> AllocError {
+    AllocError::exhausted(Layout::new::<u64>())
+}
+
+#[test]
+fn order() {
+    let _guard = setup(true);
+
+    assert!(alloc_oom::reclaim(&error()));
+    assert_eq!(calls(), [0, 1, 2, 3]);
+
+    FREE.store(false, Ordering::Relaxed);
+    assert!(!alloc_oom::reclaim(&error()));
+    assert_eq!(calls(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn full() {
+    let _guard = setup(true);
+
+    fn extra(_: &AllocError) -> bool {
+        true
+    }
+
+    let hook = alloc_oom::register_reclaim(extra).unwrap_err();
+    assert_eq!(hook as usize, extra as Reclaim as usize);
+
+    // the rejected hook never runs
+    alloc_oom::reclaim(&error());
+    assert_eq!(calls(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn retry() {
+    let _guard = setup(true);
+
+    let mut attempts = 0;
+    let x = alloc_oom::retry(|| {
+        attempts += 1;
+        if attempts < 3 {
+            Err(error())
+        } else {
+            Ok(attempts)
+        }
+    });
+
+    assert_eq!(x, 3);
+    // the hooks ran once per failed attempt
+    assert_eq!(calls(), [0, 1, 2, 3, 0, 1, 2, 3]);
+}
+
+#[test]
+fn retry_fails() {
+    let _guard = setup(false);
+
+    let payload = panic::catch_unwind(|| alloc_oom::retry(|| Err::<(), _>(error()))).unwrap_err();
+
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("out of memory"), "{}", message);
+    assert_eq!(calls(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn retry_bounded() {
+    let _guard = setup(true);
+
+    // the hooks claim to free memory but the request never succeeds
+    panic::catch_unwind(|| alloc_oom::retry(|| Err::<(), _>(error()))).unwrap_err();
+
+    assert_eq!(calls().len(), MAX_RECLAIM_ROUNDS * MAX_RECLAIM_HOOKS);
+}
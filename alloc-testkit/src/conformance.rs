@@ -0,0 +1,466 @@
+//! Conformance checks for `Alloc` implementations
+//!
+//! Each check receives a fresh allocator and panics, with a message that describes the broken
+//! contract, if the allocator misbehaves. [`check`] runs all of them.
+//!
+//! ```
+//! use std::alloc::System;
+//!
+//! use alloc_testkit::conformance;
+//! use alloc_trait::Global;
+//!
+//! conformance::check(|| Global(System));
+//! ```
+
+use core::{alloc::Layout, ptr::NonNull, slice};
+
+use alloc_trait::{Alloc, Allocator, Excess};
+
+/// Maximum number of blocks that are live at any point of a check
+const MAX_LIVE: usize = 64;
+
+/// Parameters of the conformance checks
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Seed of the pseudo-random number generator that picks the operations and layouts
+    pub seed: u32,
+    /// Number of operations performed by [`operations`]
+    pub operations: usize,
+    /// Largest size requested, in bytes
+    pub max_size: usize,
+    /// Largest alignment requested; must be a power of two
+    pub max_align: usize,
+    /// Whether [`check_with`] runs [`reuse`]; turn this off for bump allocators
+    pub reuse: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: 0x5eed,
+            operations: 1_000,
+            max_size: 256,
+            max_align: 64,
+            reuse: true,
+        }
+    }
+}
+
+/// Runs all the checks using the default [`Config`]
+///
+/// `new` must return an allocator in its initial state every time it's called
+pub fn check<A>(new: impl FnMut() -> A)
+where
+    A: Alloc,
+{
+    check_with(&Config::default(), new)
+}
+
+/// Runs all the checks using the given `config`
+pub fn check_with<A>(config: &Config, mut new: impl FnMut() -> A)
+where
+    A: Alloc,
+{
+    operations(config, &mut new());
+    if config.reuse {
+        reuse(config, &mut new());
+    }
+    zero_sized(config, &mut new());
+    huge(config, &mut new());
+}
+
+/// Performs random `alloc`, `dealloc`, `realloc`, `grow_in_place` and `shrink_in_place` operations
+///
+/// Checks that:
+///
+/// - returned blocks satisfy the requested alignment and span at least the requested size
+/// - live blocks don't overlap
+/// - the contents of a block survive `realloc`, `grow_in_place` and `shrink_in_place`
+/// - a block keeps its contents, and remains usable with its old layout, when one of these
+///   operations fails
+pub fn operations<A>(config: &Config, alloc: &mut A)
+where
+    A: Alloc,
+{
+    let mut rng = Rng::new(config.seed);
+    let mut live = Live::new();
+
+    for _ in 0..config.operations {
+        match rng.below(5) {
+            0 if live.blocks.len() < MAX_LIVE => {
+                let layout = rng.layout(config);
+                if let Ok(Excess(ptr, usable)) = unsafe { alloc.alloc_excess(layout) } {
+                    live.insert(ptr, layout, usable, "alloc_excess");
+                }
+            }
+
+            1 => {
+                if let Some(block) = live.remove(&mut rng) {
+                    block.verify("dealloc");
+                    unsafe { alloc.dealloc(block.ptr, block.layout) }
+                }
+            }
+
+            2 => {
+                if let Some(block) = live.remove(&mut rng) {
+                    let new_size = rng.size(config);
+                    block.verify("realloc");
+
+                    match unsafe { alloc.realloc_excess(block.ptr, block.layout, new_size) } {
+                        Ok(Excess(ptr, usable)) => {
+                            let layout = resized(block.layout, new_size);
+                            let moved = Block {
+                                ptr,
+                                layout,
+                                ..block
+                            };
+                            moved.verify_prefix(block.layout.size(), "realloc");
+                            live.insert(ptr, layout, usable, "realloc_excess");
+                        }
+
+                        Err(_) => {
+                            block.verify("a failed realloc");
+                            live.blocks.push(block);
+                        }
+                    }
+                }
+            }
+
+            3 => {
+                if let Some(block) = live.remove(&mut rng) {
+                    let new_size = block.layout.size() + rng.size(config);
+
+                    if unsafe { alloc.grow_in_place(block.ptr, block.layout, new_size) }.is_ok() {
+                        block.verify("grow_in_place");
+                        let usable = new_size.max(block.usable);
+                        live.insert(
+                            block.ptr,
+                            resized(block.layout, new_size),
+                            usable,
+                            "grow_in_place",
+                        );
+                    } else {
+                        block.verify("a failed grow_in_place");
+                        live.blocks.push(block);
+                    }
+                }
+            }
+
+            _ => {
+                if let Some(block) = live.remove(&mut rng) {
+                    let new_size = 1 + rng.below(block.layout.size());
+
+                    if unsafe { alloc.shrink_in_place(block.ptr, block.layout, new_size) }.is_ok() {
+                        let layout = resized(block.layout, new_size);
+                        let shrunk = Block { layout, ..block };
+                        shrunk.verify("shrink_in_place");
+                        // NOTE the allocator may have reclaimed the tail
+                        live.insert(block.ptr, layout, new_size, "shrink_in_place");
+                    } else {
+                        block.verify("a failed shrink_in_place");
+                        live.blocks.push(block);
+                    }
+                }
+            }
+        }
+    }
+
+    live.free(alloc);
+}
+
+/// Checks that freed memory can be allocated again
+///
+/// Allocates blocks of random layouts until the allocator runs out of memory (or up to an internal
+/// limit), frees all of them and then makes the same requests again; the second round must succeed
+/// as many times as the first one did. This is done twice: freeing the blocks in allocation order
+/// and then in reverse order.
+pub fn reuse<A>(config: &Config, alloc: &mut A)
+where
+    A: Alloc,
+{
+    let mut rng = Rng::new(config.seed);
+    let layouts = (0..4 * MAX_LIVE)
+        .map(|_| rng.layout(config))
+        .collect::<Vec<_>>();
+
+    let mut first = Live::new();
+    for layout in &layouts {
+        match unsafe { alloc.alloc_excess(*layout) } {
+            Ok(Excess(ptr, usable)) => first.insert(ptr, *layout, usable, "alloc_excess"),
+            Err(_) => break,
+        }
+    }
+    let n = first.blocks.len();
+    first.free(alloc);
+
+    for round in 0..2 {
+        let mut again = Live::new();
+        for (i, layout) in layouts[..n].iter().enumerate() {
+            match unsafe { alloc.alloc_excess(*layout) } {
+                Ok(Excess(ptr, usable)) => again.insert(ptr, *layout, usable, "alloc_excess"),
+                Err(e) => panic!(
+                    "freed memory was not reused: request #{} ({:?}) failed after freeing all \
+                     blocks: {}",
+                    i, layout, e
+                ),
+            }
+        }
+
+        if round != 0 {
+            again.blocks.reverse();
+        }
+        again.free(alloc);
+    }
+}
+
+/// Checks zero-sized requests made through the [`Allocator`] interface
+///
+/// Zero-sized blocks must be well aligned and must be usable as the input of `grow` and `shrink`
+pub fn zero_sized<A>(config: &Config, alloc: &mut A)
+where
+    A: Alloc,
+{
+    let mut align = 1;
+    while align <= config.max_align {
+        let zero = Layout::from_size_align(0, align).unwrap();
+        let block = Allocator::allocate(alloc, zero)
+            .unwrap_or_else(|e| panic!("zero-sized request ({:?}) failed: {}", zero, e));
+        assert_aligned(block.cast(), zero, "allocate");
+        unsafe { Allocator::deallocate(alloc, block.cast(), zero) }
+
+        // zero -> non-zero -> zero
+        let block = Allocator::allocate(alloc, zero).unwrap();
+        let layout = Layout::from_size_align(align, align).unwrap();
+        if let Ok(grown) = unsafe { Allocator::grow(alloc, block.cast(), zero, layout) } {
+            assert_aligned(grown.cast(), layout, "grow");
+            assert!(
+                grown.len() >= layout.size(),
+                "grow returned a block smaller than requested ({:?})",
+                layout
+            );
+            unsafe { grown.cast::<u8>().as_ptr().write_bytes(0xaa, layout.size()) }
+
+            let shrunk = unsafe { Allocator::shrink(alloc, grown.cast(), layout, zero) }
+                .unwrap_or_else(|e| panic!("shrinking a block to zero bytes failed: {}", e));
+            assert_aligned(shrunk.cast(), zero, "shrink");
+            unsafe { Allocator::deallocate(alloc, shrunk.cast(), zero) }
+        }
+
+        align *= 2;
+    }
+}
+
+/// Checks that requests that can't possibly be served fail gracefully
+///
+/// Requests of (almost) `isize::MAX` bytes must return an error rather than panic or hand out a
+/// block; a block that fails to be resized to such a size must keep its contents
+pub fn huge<A>(config: &Config, alloc: &mut A)
+where
+    A: Alloc,
+{
+    let mut align = 1;
+    while align <= config.max_align {
+        let size = isize::MAX as usize + 1 - align;
+        let layout = Layout::from_size_align(size, align).unwrap();
+
+        assert!(
+            unsafe { alloc.alloc(layout) }.is_err(),
+            "huge request ({:?}) didn't fail",
+            layout
+        );
+        assert!(
+            unsafe { alloc.alloc_excess(layout) }.is_err(),
+            "huge request ({:?}) didn't fail",
+            layout
+        );
+        let _ = alloc.usable_size(&layout);
+
+        let small = Layout::from_size_align(align, align).unwrap();
+        if let Ok(Excess(ptr, usable)) = unsafe { alloc.alloc_excess(small) } {
+            let block = Block::new(ptr, small, usable, 0x5a, "alloc_excess");
+
+            assert!(
+                unsafe { alloc.grow_in_place(ptr, small, size) }.is_err(),
+                "growing a block to {} bytes in place didn't fail",
+                size
+            );
+            block.verify("a failed grow_in_place");
+
+            assert!(
+                unsafe { alloc.realloc(ptr, small, size) }.is_err(),
+                "reallocating a block to {} bytes didn't fail",
+                size
+            );
+            block.verify("a failed realloc");
+
+            unsafe { alloc.dealloc(ptr, small) }
+        }
+
+        align *= 2;
+    }
+}
+
+/// A live block; its first `layout.size()` bytes are filled with `tag`
+struct Block {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    /// Number of bytes, starting at `ptr`, that belong to the block
+    usable: usize,
+    tag: u8,
+}
+
+impl Block {
+    fn new(ptr: NonNull<u8>, layout: Layout, usable: usize, tag: u8, op: &str) -> Self {
+        assert_aligned(ptr, layout, op);
+        assert!(
+            usable >= layout.size(),
+            "{} reported a usable size ({}) smaller than the requested size ({:?})",
+            op,
+            usable,
+            layout
+        );
+
+        // the whole usable area must be writable
+        unsafe { ptr.as_ptr().write_bytes(tag, usable) }
+
+        Self {
+            ptr,
+            layout,
+            usable,
+            tag,
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.ptr.as_ptr() as usize
+    }
+
+    fn end(&self) -> usize {
+        self.start() + self.usable
+    }
+
+    /// Checks that the contents of the block were preserved by `op`
+    fn verify(&self, op: &str) {
+        self.verify_prefix(self.layout.size(), op)
+    }
+
+    /// Checks that the first `len` bytes of the block were preserved by `op`
+    fn verify_prefix(&self, len: usize, op: &str) {
+        let len = len.min(self.layout.size());
+        let bytes = unsafe { slice::from_raw_parts(self.ptr.as_ptr(), len) };
+        if let Some(i) = bytes.iter().position(|byte| *byte != self.tag) {
+            panic!(
+                "byte #{} of the block at {:?} ({:?}) was corrupted after {}: expected {:#04x}, \
+                 found {:#04x}",
+                i, self.ptr, self.layout, op, self.tag, bytes[i]
+            );
+        }
+    }
+}
+
+struct Live {
+    blocks: Vec<Block>,
+    /// Tag of the next block
+    tag: u8,
+}
+
+impl Live {
+    fn new() -> Self {
+        Self {
+            blocks: vec![],
+            tag: 0,
+        }
+    }
+
+    /// Tracks a block returned by `op`; checks that it doesn't overlap any other live block
+    fn insert(&mut self, ptr: NonNull<u8>, layout: Layout, usable: usize, op: &str) {
+        // NOTE the tags are never zero so zeroed memory is not mistaken for a block's contents
+        self.tag = self.tag.wrapping_add(1).max(1);
+
+        let block = Block::new(ptr, layout, usable, self.tag, op);
+        if let Some(other) = self
+            .blocks
+            .iter()
+            .find(|other| block.start() < other.end() && other.start() < block.end())
+        {
+            panic!(
+                "{} returned a block at {:?} ({} bytes) that overlaps the live block at {:?} ({} \
+                 bytes)",
+                op, block.ptr, block.usable, other.ptr, other.usable
+            );
+        }
+
+        self.blocks.push(block);
+    }
+
+    /// Untracks a random block
+    fn remove(&mut self, rng: &mut Rng) -> Option<Block> {
+        if self.blocks.is_empty() {
+            None
+        } else {
+            let i = rng.below(self.blocks.len());
+            Some(self.blocks.swap_remove(i))
+        }
+    }
+
+    /// Frees all the blocks, in order
+    fn free<A>(self, alloc: &mut A)
+    where
+        A: Alloc,
+    {
+        for block in self.blocks {
+            block.verify("dealloc");
+            unsafe { alloc.dealloc(block.ptr, block.layout) }
+        }
+    }
+}
+
+fn assert_aligned(ptr: NonNull<u8>, layout: Layout, op: &str) {
+    assert!(
+        ptr.as_ptr() as usize & (layout.align() - 1) == 0,
+        "{} returned a misaligned block: {:?} ({:?})",
+        op,
+        ptr,
+        layout
+    );
+}
+
+fn resized(layout: Layout, new_size: usize) -> Layout {
+    Layout::from_size_align(new_size, layout.align()).unwrap()
+}
+
+/// xorshift32
+struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    fn new(seed: u32) -> Self {
+        // NOTE xorshift gets stuck at zero
+        Self { state: seed.max(1) }
+    }
+
+    fn next(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Returns a number in the range `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        self.next() as usize % n
+    }
+
+    fn size(&mut self, config: &Config) -> usize {
+        1 + self.below(config.max_size)
+    }
+
+    fn layout(&mut self, config: &Config) -> Layout {
+        let shifts = config.max_align.trailing_zeros() as usize + 1;
+        let align = 1 << self.below(shifts);
+        Layout::from_size_align(self.size(config), align).unwrap()
+    }
+}
@@ -0,0 +1,53 @@
+//! Host-side testing tools for `Alloc` implementations and their users
+//!
+//! **NOTE** this crate depends on `std`; it's meant to be used in tests that run on the host
+//!
+//! # Conformance checks
+//!
+//! The [`conformance`] module checks that an `Alloc` implementation honors the contracts of the
+//! trait. Run it against every new allocator.
+//!
+//! # Out-Of-Memory sweeps
+//!
+//! [`sweep`] runs a scenario once per allocation request the scenario makes, failing a different
+//! request each time. This exercises every Out-Of-Memory path of the scenario exactly once.
+//!
+//! The collections in the `collections` crate call the `#[oom]` handler when an allocation fails
+//! so the test binary must declare an `#[oom]` handler that panics or that returns
+//! `Decision::Fail`, which makes infallible operations like `Vec::push` panic:
+//!
+//! ```
+//! use alloc_oom::{oom, AllocError};
+//! use alloc_trait::Global;
+//! use collections::Vec;
+//! use std::alloc::System;
+//!
+//! #[oom]
+//! fn on_oom(error: AllocError) -> ! {
+//!     panic!("{}", error)
+//! }
+//!
+//! let outcomes = alloc_testkit::sweep(
+//!     || Global(System),
+//!     |a| {
+//!         let mut xs = Vec::new(a);
+//!         for i in 0..10 {
+//!             xs.push(i);
+//!         }
+//!     },
+//! );
+//!
+//! // `Vec` can't recover from an OOM condition
+//! assert!(outcomes.iter().all(|outcome| outcome.result.is_err()));
+//! ```
+
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+pub use crate::shared::Shared;
+pub use crate::sweep::{sweep, Outcome};
+
+pub mod conformance;
+
+mod shared;
+mod sweep;
@@ -0,0 +1,116 @@
+use core::{alloc::Layout, cell::RefCell, fmt, ptr::NonNull};
+
+use alloc_trait::{Alloc, AllocError, Excess, Owns};
+
+/// A `Copy` handle to an allocator
+///
+/// All the copies of the handle use the same allocator, like the handles declared with
+/// `cortex_m_tm_alloc::allocator` do, so several collections can share one allocator.
+///
+/// **NOTE** the allocator is leaked: it will never be dropped
+pub struct Shared<A>
+where
+    A: 'static,
+{
+    inner: &'static RefCell<A>,
+}
+
+impl<A> Shared<A> {
+    /// Moves `alloc` into the heap and returns a handle to it
+    pub fn new(alloc: A) -> Self {
+        Self {
+            inner: Box::leak(Box::new(RefCell::new(alloc))),
+        }
+    }
+
+    /// Grants `f` access to the allocator
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `f` uses the allocator through a copy of this handle
+    pub fn with<R>(&self, f: impl FnOnce(&mut A) -> R) -> R {
+        f(&mut self.inner.borrow_mut())
+    }
+}
+
+impl<A> Clone for Shared<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A> Copy for Shared<A> {}
+
+impl<A> fmt::Debug for Shared<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("inner", &(self.inner as *const RefCell<A>))
+            .finish()
+    }
+}
+
+impl<A> Alloc for Shared<A>
+where
+    A: Alloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.with(|a| a.alloc(layout))
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        self.with(|a| a.dealloc(ptr, layout))
+    }
+
+    fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+        self.inner.borrow().usable_size(layout)
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        self.with(|a| a.alloc_excess(layout))
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        self.with(|a| a.grow_in_place(ptr, layout, new_size))
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        self.with(|a| a.shrink_in_place(ptr, layout, new_size))
+    }
+
+    unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.with(|a| a.realloc(ptr, layout, new_size))
+    }
+
+    unsafe fn realloc_excess(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<Excess, AllocError> {
+        self.with(|a| a.realloc_excess(ptr, layout, new_size))
+    }
+}
+
+impl<A> Owns for Shared<A>
+where
+    A: Owns,
+{
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.inner.borrow().owns(ptr, layout)
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+};
+
+use alloc_trait::{Alloc, FailingAlloc, Schedule};
+
+use crate::Shared;
+
+/// The result of running a scenario with one failed allocation request
+#[derive(Debug)]
+pub struct Outcome {
+    /// Number of the allocation request that was made to fail, counting from 0
+    pub nth: usize,
+    /// `Ok` if the scenario ran to completion; otherwise the panic message
+    pub result: Result<(), String>,
+}
+
+/// Runs `scenario` once per allocation request it makes, failing the `n`-th request on the `n`-th
+/// run
+///
+/// `new` creates the allocator used on each run. The sweep ends with a run that completes without
+/// hitting any injected failure; that run is not included in the returned outcomes.
+///
+/// # Panics
+///
+/// This function panics if `scenario` panics on a run where no failure was injected
+pub fn sweep<A, S>(mut new: impl FnMut() -> A, scenario: S) -> Vec<Outcome>
+where
+    A: Alloc + 'static,
+    S: Fn(Shared<FailingAlloc<A>>),
+{
+    let mut outcomes = vec![];
+
+    for nth in 0.. {
+        let alloc = Shared::new(FailingAlloc::new(new(), Schedule::Nth(nth)));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| scenario(alloc)));
+
+        if alloc.with(|a| a.failures()) == 0 {
+            // the scenario made fewer than `nth + 1` requests; all of them have been exercised
+            if let Err(payload) = result {
+                panic::resume_unwind(payload)
+            }
+
+            break;
+        }
+
+        outcomes.push(Outcome {
+            nth,
+            result: result.map_err(message),
+        });
+    }
+
+    outcomes
+}
+
+fn message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic payload>".to_owned()
+    }
+}
@@ -0,0 +1,62 @@
+use alloc_testkit::conformance::{self, Config};
+use alloc_trait::NullAlloc;
+use arena::Arena;
+use buddy::Buddy;
+use pool::{Policy, SizeClass, SizeClassPool, SizeClasses};
+use tlsf::Tlsf;
+
+#[repr(align(4096))]
+struct Memory([u8; 4096]);
+
+// NOTE each allocator gets its own memory, which is leaked
+fn memory() -> &'static mut [u8] {
+    &mut Box::leak(Box::new(Memory([0; 4096]))).0
+}
+
+#[test]
+fn tlsf() {
+    conformance::check(|| {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        tlsf.extend(memory()).unwrap();
+        tlsf
+    });
+
+    conformance::check(|| {
+        let mut tlsf = Tlsf::<12, 16>::new();
+        tlsf.extend(memory()).unwrap();
+        tlsf
+    });
+}
+
+#[test]
+fn arena() {
+    conformance::check_with(
+        &Config {
+            // a bump allocator only reuses the last block
+            reuse: false,
+            ..Config::default()
+        },
+        || Arena::new(memory()),
+    );
+}
+
+#[test]
+fn pool() {
+    conformance::check(|| {
+        SizeClassPool::new(
+            memory(),
+            SizeClasses::new([
+                SizeClass::new(16, 32),
+                SizeClass::new(64, 16),
+                SizeClass::new(256, 8),
+            ]),
+            Policy::Larger,
+            NullAlloc,
+        )
+    });
+}
+
+#[test]
+fn buddy() {
+    conformance::check(|| Buddy::<4>::new(memory()));
+}
@@ -0,0 +1,90 @@
+use std::alloc::System;
+
+use core::alloc::Layout;
+
+use alloc_oom::{oom, AllocError, Decision};
+use alloc_testkit::sweep;
+use alloc_trait::{Alloc, Global};
+use collections::Vec;
+
+// report the error to fallible callers; infallible ones panic
+#[oom]
+fn on_oom(_error: AllocError) -> Decision {
+    Decision::Fail
+}
+
+#[test]
+fn push() {
+    let outcomes = sweep(
+        || Global(System),
+        |a| {
+            let mut xs = Vec::new(a);
+            for i in 0..10 {
+                xs.push(i);
+            }
+            assert_eq!(xs.len(), 10);
+        },
+    );
+
+    // the capacity grows 1 -> 2 -> 4 -> 8 -> 16
+    assert_eq!(
+        outcomes
+            .iter()
+            .map(|outcome| outcome.nth)
+            .collect::<std::vec::Vec<_>>(),
+        [0, 1, 2, 3, 4]
+    );
+    for outcome in &outcomes {
+        let message = outcome.result.as_ref().unwrap_err();
+        assert!(
+            message.starts_with("memory allocation failed"),
+            "{}",
+            message
+        );
+    }
+}
+
+#[test]
+fn fallible() {
+    let outcomes = sweep(
+        || Global(System),
+        |mut a| {
+            let layout = Layout::new::<u64>();
+            let mut blocks = std::vec::Vec::new();
+            for _ in 0..3 {
+                match unsafe { a.alloc(layout) } {
+                    Ok(block) => blocks.push(block),
+                    // degrade gracefully
+                    Err(_) => break,
+                }
+            }
+
+            for block in blocks {
+                unsafe { a.dealloc(block, layout) }
+            }
+        },
+    );
+
+    assert_eq!(outcomes.len(), 3);
+    assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+}
+
+#[test]
+fn try_reserve() {
+    let outcomes = sweep(
+        || Global(System),
+        |a| {
+            let mut xs = Vec::<u32, _>::new(a);
+            for _ in 0..3 {
+                if xs.try_reserve(xs.capacity() + 1).is_err() {
+                    // degrade gracefully
+                    return;
+                }
+            }
+            assert!(xs.capacity() >= 3);
+        },
+    );
+
+    assert_eq!(outcomes.len(), 3);
+    assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+}
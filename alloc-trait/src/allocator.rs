@@ -0,0 +1,289 @@
+use core::{
+    alloc::Layout,
+    cmp,
+    ptr::{self, NonNull},
+};
+
+use crate::{Alloc, AllocError, AllocErrorKind, Excess};
+
+/// See [`core::alloc::Allocator`][0]
+///
+/// Unlike `core`'s version the methods of this trait take `&mut self`, like the methods of
+/// [`Alloc`] do; allocators that must be shared, like the ones declared with
+/// `cortex_m_tm_alloc::allocator`, are cheap `Copy` handles.
+///
+/// Every [`Alloc`] implementer also implements this trait.
+///
+/// A block is *currently allocated* from the moment `allocate`, `grow` or `shrink` returns it until
+/// it's passed to `deallocate`, `grow` or `shrink`. A layout *fits* a block if it has the alignment
+/// the block was requested with and a size between the requested size and the length of the
+/// returned slice.
+///
+/// # Safety
+///
+/// A currently allocated block must stay valid and must not overlap any other currently
+/// allocated block. The returned slices must satisfy the alignment of the requested layout and be
+/// at least as large as the requested size. A handle and its copies are the same allocator: a
+/// block allocated through one copy can be freed through another.
+///
+/// [0]: https://doc.rust-lang.org/core/alloc/trait.Allocator.html
+pub unsafe trait Allocator {
+    /// See [`core::alloc::Allocator.allocate`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Allocator.html#tymethod.allocate
+    fn allocate(&mut self, layout: Layout) -> Result<NonNull<[u8]>, AllocError>;
+
+    /// See [`core::alloc::Allocator.allocate_zeroed`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Allocator.html#method.allocate_zeroed
+    fn allocate_zeroed(&mut self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let block = self.allocate(layout)?;
+        unsafe { ptr::write_bytes(block.cast::<u8>().as_ptr(), 0, block.len()) }
+        Ok(block)
+    }
+
+    /// See [`core::alloc::Allocator.deallocate`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Allocator.html#tymethod.deallocate
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block currently allocated by this allocator and `layout` must fit that
+    /// block
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout);
+
+    /// Attempts to extend the block in place; the block keeps its address
+    ///
+    /// The default implementation always fails
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block currently allocated by this allocator, `old_layout` must fit that
+    /// block and `new_layout.size()` must be greater than or equal to `old_layout.size()`
+    unsafe fn grow_in_place(
+        &mut self,
+        _ptr: NonNull<u8>,
+        _old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(), AllocError> {
+        Err(AllocError::exhausted(new_layout))
+    }
+
+    /// Attempts to shrink the block in place; the block keeps its address
+    ///
+    /// The default implementation always fails
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block currently allocated by this allocator, `old_layout` must fit that
+    /// block and `new_layout.size()` must be smaller than or equal to `old_layout.size()`
+    unsafe fn shrink_in_place(
+        &mut self,
+        _ptr: NonNull<u8>,
+        _old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(), AllocError> {
+        Err(AllocError::exhausted(new_layout))
+    }
+
+    /// See [`core::alloc::Allocator.grow`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Allocator.html#method.grow
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Allocator::grow_in_place`]. On success the block at `ptr` is no longer allocated,
+    /// even if the returned block has the same address
+    unsafe fn grow(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(new_layout.size() >= old_layout.size());
+
+        if new_layout.align() == old_layout.align()
+            && self.grow_in_place(ptr, old_layout, new_layout).is_ok()
+        {
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        move_block(self, ptr, old_layout, new_layout)
+    }
+
+    /// See [`core::alloc::Allocator.grow_zeroed`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Allocator.html#method.grow_zeroed
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Allocator::grow_in_place`]. On success the block at `ptr` is no longer allocated,
+    /// even if the returned block has the same address
+    unsafe fn grow_zeroed(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let block = self.grow(ptr, old_layout, new_layout)?;
+        let old_size = old_layout.size();
+        ptr::write_bytes(
+            block.cast::<u8>().as_ptr().add(old_size),
+            0,
+            block.len() - old_size,
+        );
+        Ok(block)
+    }
+
+    /// See [`core::alloc::Allocator.shrink`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Allocator.html#method.shrink
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Allocator::shrink_in_place`]. On success the block at `ptr` is no longer
+    /// allocated, even if the returned block has the same address
+    unsafe fn shrink(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        debug_assert!(new_layout.size() <= old_layout.size());
+
+        if new_layout.align() == old_layout.align()
+            && self.shrink_in_place(ptr, old_layout, new_layout).is_ok()
+        {
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        move_block(self, ptr, old_layout, new_layout)
+    }
+}
+
+// `allocate` + `copy` + `deallocate`
+unsafe fn move_block<A>(
+    allocator: &mut A,
+    ptr: NonNull<u8>,
+    old_layout: Layout,
+    new_layout: Layout,
+) -> Result<NonNull<[u8]>, AllocError>
+where
+    A: Allocator + ?Sized,
+{
+    let block = allocator.allocate(new_layout)?;
+    ptr::copy_nonoverlapping(
+        ptr.as_ptr(),
+        block.cast::<u8>().as_ptr(),
+        cmp::min(old_layout.size(), new_layout.size()),
+    );
+    allocator.deallocate(ptr, old_layout);
+    Ok(block)
+}
+
+// a well-aligned dangling pointer used for zero-sized allocations
+pub(crate) fn dangling(layout: Layout) -> NonNull<[u8]> {
+    unsafe { NonNull::slice_from_raw_parts(NonNull::new_unchecked(layout.align() as *mut u8), 0) }
+}
+
+/// Compatibility shim: `Alloc` implementers are `Allocator`s
+///
+/// Zero-sized requests are handled here and never reach the `Alloc` implementation
+unsafe impl<A> Allocator for A
+where
+    A: Alloc,
+{
+    fn allocate(&mut self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout));
+        }
+
+        unsafe {
+            self.alloc_excess(layout)
+                .map(|Excess(ptr, size)| NonNull::slice_from_raw_parts(ptr, size))
+        }
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.dealloc(ptr, layout)
+        }
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(), AllocError> {
+        if old_layout.size() == 0 {
+            return Err(AllocError::exhausted(new_layout));
+        }
+
+        if new_layout.align() != old_layout.align() {
+            return Err(AllocError::new(
+                new_layout,
+                AllocErrorKind::UnsupportedAlignment,
+            ));
+        }
+
+        Alloc::grow_in_place(self, ptr, old_layout, new_layout.size())
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<(), AllocError> {
+        if new_layout.size() == 0 {
+            return Err(AllocError::exhausted(new_layout));
+        }
+
+        if new_layout.align() != old_layout.align() {
+            return Err(AllocError::new(
+                new_layout,
+                AllocErrorKind::UnsupportedAlignment,
+            ));
+        }
+
+        Alloc::shrink_in_place(self, ptr, old_layout, new_layout.size())
+    }
+
+    unsafe fn grow(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.size() == 0 {
+            return self.allocate(new_layout);
+        }
+
+        if new_layout.align() == old_layout.align() {
+            self.realloc_excess(ptr, old_layout, new_layout.size())
+                .map(|Excess(ptr, size)| NonNull::slice_from_raw_parts(ptr, size))
+        } else {
+            move_block(self, ptr, old_layout, new_layout)
+        }
+    }
+
+    unsafe fn shrink(
+        &mut self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if new_layout.size() == 0 {
+            self.deallocate(ptr, old_layout);
+            return Ok(dangling(new_layout));
+        }
+
+        if new_layout.align() == old_layout.align() {
+            self.realloc_excess(ptr, old_layout, new_layout.size())
+                .map(|Excess(ptr, size)| NonNull::slice_from_raw_parts(ptr, size))
+        } else {
+            move_block(self, ptr, old_layout, new_layout)
+        }
+    }
+}
@@ -0,0 +1,604 @@
+use core::{
+    alloc::Layout,
+    cmp,
+    ptr::{self, NonNull},
+};
+
+use crate::{Alloc, AllocError, AllocErrorKind, Owns};
+
+/// Number of guard bytes placed before and after each block
+const GUARD: usize = 8;
+/// Value of the guard bytes
+const CANARY: u8 = 0xA5;
+/// Value written into freed blocks
+const POISON: u8 = 0xDD;
+
+/// Wrapper that detects misuse of the inner allocator
+///
+/// The following bugs are detected and passed to the report hook:
+///
+/// - freeing a block twice
+/// - freeing a pointer that was not allocated by this allocator
+/// - freeing a block with a `Layout` different from the one used to allocate it
+/// - writing past either end of a block; detected through guard bytes when the block is freed
+/// - writing to a block after freeing it; freed blocks are filled with a poison value and
+///   quarantined. The poison is verified when a block leaves the quarantine
+/// - calling into the allocator while an operation is in progress, e.g. from an interrupt handler.
+///   This check is best-effort: it's a plain flag, not an atomic, and a reentrant call can only
+///   happen if the allocator was aliased, which the compiler assumes doesn't happen, so some
+///   reentrant calls may go unnoticed. A reentrant call fails with
+///   [`AllocErrorKind::Reentrant`], or does nothing in the case of `dealloc`
+///
+/// `LIVE` is the maximum number of blocks that can be allocated at any time; allocations past
+/// that limit fail. `QUARANTINE` is the number of freed blocks that are held back before being
+/// returned to the inner allocator.
+///
+/// Each block uses `2 * 8` guard bytes (more for alignments larger than 8) of the inner allocator.
+pub struct Checked<A, const LIVE: usize = 32, const QUARANTINE: usize = 4> {
+    inner: A,
+    report: fn(Violation),
+    live: [Option<Block>; LIVE],
+    quarantine: [Option<Block>; QUARANTINE],
+    /// Next `quarantine` slot to use
+    next: usize,
+    /// An operation is in progress; best-effort reentrancy detection
+    busy: bool,
+}
+
+/// A misuse of the allocator detected by [`Checked`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Violation {
+    /// A block was freed twice
+    DoubleFree {
+        /// Address of the block
+        ptr: NonNull<u8>,
+    },
+    /// A pointer not allocated by this allocator (or already returned to the inner allocator) was
+    /// freed
+    ForeignFree {
+        /// The freed pointer
+        ptr: NonNull<u8>,
+        /// The layout passed to `dealloc`
+        layout: Layout,
+    },
+    /// A block was freed with a layout different from the one it was allocated with
+    LayoutMismatch {
+        /// Address of the block
+        ptr: NonNull<u8>,
+        /// The layout passed to `alloc`
+        allocated: Layout,
+        /// The layout passed to `dealloc`
+        freed: Layout,
+    },
+    /// The guard bytes before the block were overwritten
+    Underrun {
+        /// Address of the block
+        ptr: NonNull<u8>,
+        /// Layout of the block
+        layout: Layout,
+    },
+    /// The guard bytes after the block were overwritten
+    Overrun {
+        /// Address of the block
+        ptr: NonNull<u8>,
+        /// Layout of the block
+        layout: Layout,
+    },
+    /// A freed block was written to
+    UseAfterFree {
+        /// Address of the block
+        ptr: NonNull<u8>,
+        /// Layout of the block
+        layout: Layout,
+    },
+    /// The allocator was called while another of its operations was in progress
+    Reentrant,
+}
+
+#[derive(Clone, Copy)]
+struct Block {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl Block {
+    /// Bytes between the start of the inner block and `ptr`
+    fn front(&self) -> usize {
+        front(self.layout)
+    }
+
+    unsafe fn inner_ptr(&self) -> NonNull<u8> {
+        NonNull::new_unchecked(self.ptr.as_ptr().sub(self.front()))
+    }
+
+    unsafe fn inner_layout(&self) -> Layout {
+        // NOTE this layout was validated by `inner_layout` when the block was allocated
+        Layout::from_size_align_unchecked(
+            self.front() + self.layout.size() + GUARD,
+            self.layout.align(),
+        )
+    }
+
+    unsafe fn check_guards(&self) -> Option<Violation> {
+        let p = self.ptr.as_ptr();
+        let front = self.front();
+
+        if (1..=front).any(|i| *p.sub(i) != CANARY) {
+            Some(Violation::Underrun {
+                ptr: self.ptr,
+                layout: self.layout,
+            })
+        } else if (0..GUARD).any(|i| *p.add(self.layout.size() + i) != CANARY) {
+            Some(Violation::Overrun {
+                ptr: self.ptr,
+                layout: self.layout,
+            })
+        } else {
+            None
+        }
+    }
+
+    unsafe fn check_poison(&self) -> Option<Violation> {
+        let p = self.ptr.as_ptr();
+
+        if (0..self.layout.size()).any(|i| *p.add(i) != POISON) {
+            Some(Violation::UseAfterFree {
+                ptr: self.ptr,
+                layout: self.layout,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn front(layout: Layout) -> usize {
+    cmp::max(GUARD, layout.align())
+}
+
+fn inner_layout(layout: Layout) -> Option<Layout> {
+    let size = front(layout)
+        .checked_add(layout.size())?
+        .checked_add(GUARD)?;
+    Layout::from_size_align(size, layout.align()).ok()
+}
+
+impl<A, const LIVE: usize, const QUARANTINE: usize> Checked<A, LIVE, QUARANTINE> {
+    /// Starts checking the uses of `inner`; violations are passed to `report`
+    pub const fn new(inner: A, report: fn(Violation)) -> Self {
+        Self {
+            inner,
+            report,
+            live: [None; LIVE],
+            quarantine: [None; QUARANTINE],
+            next: 0,
+            busy: false,
+        }
+    }
+
+    /// Returns a reference to the inner allocator
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner allocator
+    ///
+    /// Operations performed directly on the inner allocator are not checked
+    pub fn inner_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+
+    /// Returns the number of blocks currently allocated
+    pub fn live_blocks(&self) -> usize {
+        self.live.iter().filter(|b| b.is_some()).count()
+    }
+
+    /// Verifies the guard bytes of every allocated block and the poison of every quarantined block
+    ///
+    /// Returns `false` if any violation was detected (and reported)
+    pub fn verify(&mut self) -> bool {
+        let mut ok = true;
+        unsafe {
+            for block in self.live.iter().flatten() {
+                if let Some(v) = block.check_guards() {
+                    (self.report)(v);
+                    ok = false;
+                }
+            }
+
+            for block in self.quarantine.iter().flatten() {
+                if let Some(v) = block.check_poison() {
+                    (self.report)(v);
+                    ok = false;
+                }
+            }
+        }
+        ok
+    }
+
+    fn enter(&mut self) -> bool {
+        if self.busy {
+            (self.report)(Violation::Reentrant);
+            false
+        } else {
+            self.busy = true;
+            true
+        }
+    }
+
+    fn exit(&mut self) {
+        self.busy = false;
+    }
+
+    /// Removes the block at `ptr` from the `live` table, reporting any violation
+    unsafe fn take(&mut self, ptr: NonNull<u8>, layout: Layout) -> Option<Block> {
+        let slot = self
+            .live
+            .iter_mut()
+            .find(|b| b.map(|b| b.ptr == ptr).unwrap_or(false));
+
+        let block = match slot.and_then(|slot| slot.take()) {
+            Some(block) => block,
+            None => {
+                let double_free = self
+                    .quarantine
+                    .iter()
+                    .flatten()
+                    .any(|block| block.ptr == ptr);
+
+                (self.report)(if double_free {
+                    Violation::DoubleFree { ptr }
+                } else {
+                    Violation::ForeignFree { ptr, layout }
+                });
+                return None;
+            }
+        };
+
+        if block.layout != layout {
+            (self.report)(Violation::LayoutMismatch {
+                ptr,
+                allocated: block.layout,
+                freed: layout,
+            });
+        }
+
+        if let Some(v) = block.check_guards() {
+            (self.report)(v);
+        }
+
+        Some(block)
+    }
+
+    /// Looks up the block at `ptr` in the `live` table, reporting any violation
+    fn find(&self, ptr: NonNull<u8>, layout: Layout) -> Option<Block> {
+        let block = self
+            .live
+            .iter()
+            .flatten()
+            .find(|block| block.ptr == ptr)
+            .copied();
+
+        match block {
+            Some(block) if block.layout != layout => (self.report)(Violation::LayoutMismatch {
+                ptr,
+                allocated: block.layout,
+                freed: layout,
+            }),
+            Some(_) => {}
+            None => (self.report)(Violation::ForeignFree { ptr, layout }),
+        }
+
+        block
+    }
+}
+
+impl<A, const LIVE: usize, const QUARANTINE: usize> Checked<A, LIVE, QUARANTINE>
+where
+    A: Alloc,
+{
+    /// Returns all the quarantined blocks to the inner allocator
+    pub fn flush(&mut self) {
+        for i in 0..QUARANTINE {
+            unsafe { self.release(i) }
+        }
+    }
+
+    /// Returns the block in the quarantine slot `i`, if any, to the inner allocator
+    unsafe fn release(&mut self, i: usize) {
+        if let Some(block) = self.quarantine[i].take() {
+            if let Some(v) = block.check_poison() {
+                (self.report)(v);
+            }
+
+            self.inner.dealloc(block.inner_ptr(), block.inner_layout());
+        }
+    }
+
+    unsafe fn alloc_checked(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let inner_layout = inner_layout(layout)
+            .ok_or_else(|| AllocError::new(layout, AllocErrorKind::SizeOverflow))?;
+
+        let slot = match self.live.iter_mut().find(|b| b.is_none()) {
+            Some(slot) => slot,
+            None => return Err(AllocError::exhausted(layout)),
+        };
+
+        let inner_ptr = self.inner.alloc(inner_layout)?;
+        let front = front(layout);
+        let ptr = NonNull::new_unchecked(inner_ptr.as_ptr().add(front));
+        ptr::write_bytes(inner_ptr.as_ptr(), CANARY, front);
+        ptr::write_bytes(ptr.as_ptr().add(layout.size()), CANARY, GUARD);
+
+        *slot = Some(Block { ptr, layout });
+        Ok(ptr)
+    }
+
+    unsafe fn dealloc_checked(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        if let Some(block) = self.take(ptr, layout) {
+            ptr::write_bytes(ptr.as_ptr(), POISON, block.layout.size());
+
+            if QUARANTINE == 0 {
+                self.inner.dealloc(block.inner_ptr(), block.inner_layout());
+            } else {
+                self.release(self.next);
+                self.quarantine[self.next] = Some(block);
+                self.next = (self.next + 1) % QUARANTINE;
+            }
+        }
+    }
+}
+
+impl<A, const LIVE: usize, const QUARANTINE: usize> Alloc for Checked<A, LIVE, QUARANTINE>
+where
+    A: Alloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if !self.enter() {
+            return Err(AllocError::new(layout, AllocErrorKind::Reentrant));
+        }
+
+        let res = self.alloc_checked(layout);
+        self.exit();
+        res
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        if !self.enter() {
+            return;
+        }
+
+        self.dealloc_checked(ptr, layout);
+        self.exit();
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+        if !self.enter() {
+            return Err(AllocError::new(new_layout, AllocErrorKind::Reentrant));
+        }
+
+        // blocks are always moved so the guard bytes stay in place
+        self.find(ptr, layout);
+        self.exit();
+        Err(AllocError::exhausted(new_layout))
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+        if !self.enter() {
+            return Err(AllocError::new(new_layout, AllocErrorKind::Reentrant));
+        }
+
+        self.find(ptr, layout);
+        self.exit();
+        Err(AllocError::exhausted(new_layout))
+    }
+
+    unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+
+        if !self.enter() {
+            return Err(AllocError::new(new_layout, AllocErrorKind::Reentrant));
+        }
+
+        let res = match self.find(ptr, layout) {
+            Some(block) => self.alloc_checked(new_layout).inspect(|new_ptr| {
+                ptr::copy_nonoverlapping(
+                    ptr.as_ptr(),
+                    new_ptr.as_ptr(),
+                    cmp::min(block.layout.size(), new_size),
+                );
+                self.dealloc_checked(ptr, block.layout);
+            }),
+            None => Err(AllocError::new(new_layout, AllocErrorKind::NotOwned)),
+        };
+
+        self.exit();
+        res
+    }
+}
+
+impl<A, const LIVE: usize, const QUARANTINE: usize> Owns for Checked<A, LIVE, QUARANTINE> {
+    fn owns(&self, ptr: NonNull<u8>, _layout: Layout) -> bool {
+        self.live.iter().flatten().any(|block| block.ptr == ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{alloc::Layout, cell::RefCell};
+    use std::{alloc::System, vec::Vec};
+
+    use super::{Checked, Violation};
+    use crate::{Alloc, AllocErrorKind, Global};
+
+    std::thread_local! {
+        static VIOLATIONS: RefCell<Vec<Violation>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn report(violation: Violation) {
+        VIOLATIONS.with(|v| v.borrow_mut().push(violation))
+    }
+
+    fn violations() -> Vec<Violation> {
+        VIOLATIONS.with(|v| v.borrow_mut().drain(..).collect())
+    }
+
+    fn checked() -> Checked<Global<System>> {
+        Checked::new(Global(System), report)
+    }
+
+    #[test]
+    fn no_violation() {
+        let mut checked = checked();
+        let layout = Layout::from_size_align(24, 8).unwrap();
+
+        unsafe {
+            let ptr = checked.alloc(layout).unwrap();
+            ptr.as_ptr().write_bytes(0, 24);
+            let ptr = checked.realloc(ptr, layout, 48).unwrap();
+            checked.dealloc(ptr, Layout::from_size_align(48, 8).unwrap());
+        }
+        checked.flush();
+
+        assert!(checked.verify());
+        assert_eq!(checked.live_blocks(), 0);
+        assert_eq!(violations(), []);
+    }
+
+    #[test]
+    fn double_free() {
+        let mut checked = checked();
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr = checked.alloc(layout).unwrap();
+            checked.dealloc(ptr, layout);
+            checked.dealloc(ptr, layout);
+
+            assert_eq!(violations(), [Violation::DoubleFree { ptr }]);
+        }
+        checked.flush();
+    }
+
+    #[test]
+    fn foreign_free() {
+        let mut checked = checked();
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr = Global(System).alloc(layout).unwrap();
+            checked.dealloc(ptr, layout);
+
+            assert_eq!(violations(), [Violation::ForeignFree { ptr, layout }]);
+            Global(System).dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn layout_mismatch() {
+        let mut checked = checked();
+        let allocated = Layout::from_size_align(16, 8).unwrap();
+        let freed = Layout::from_size_align(32, 8).unwrap();
+
+        unsafe {
+            let ptr = checked.alloc(allocated).unwrap();
+            checked.dealloc(ptr, freed);
+
+            assert_eq!(
+                violations(),
+                [Violation::LayoutMismatch {
+                    ptr,
+                    allocated,
+                    freed
+                }]
+            );
+        }
+        checked.flush();
+        assert_eq!(violations(), []);
+    }
+
+    #[test]
+    fn overrun_underrun() {
+        let mut checked = checked();
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        unsafe {
+            let ptr = checked.alloc(layout).unwrap();
+            ptr.as_ptr().add(16).write(0);
+            assert!(!checked.verify());
+            checked.dealloc(ptr, layout);
+            let overrun = Violation::Overrun { ptr, layout };
+            assert_eq!(violations(), [overrun, overrun]);
+
+            let ptr = checked.alloc(layout).unwrap();
+            ptr.as_ptr().sub(1).write(0);
+            checked.dealloc(ptr, layout);
+            assert_eq!(violations(), [Violation::Underrun { ptr, layout }]);
+        }
+        checked.flush();
+    }
+
+    #[test]
+    fn use_after_free() {
+        let mut checked = checked();
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        unsafe {
+            let ptr = checked.alloc(layout).unwrap();
+            checked.dealloc(ptr, layout);
+            // NOTE the block is quarantined so it's still allocated from the inner allocator
+            ptr.as_ptr().write(0);
+
+            checked.flush();
+            assert_eq!(violations(), [Violation::UseAfterFree { ptr, layout }]);
+        }
+    }
+
+    #[test]
+    fn reentrant() {
+        let mut checked = checked();
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr = checked.alloc(layout).unwrap();
+
+            // as if an operation had been interrupted
+            checked.busy = true;
+            let err = checked.alloc(layout).unwrap_err();
+            assert_eq!(err.kind(), AllocErrorKind::Reentrant);
+            let err = checked.grow_in_place(ptr, layout, 16).unwrap_err();
+            assert_eq!(err.kind(), AllocErrorKind::Reentrant);
+            let err = checked.realloc(ptr, layout, 16).unwrap_err();
+            assert_eq!(err.kind(), AllocErrorKind::Reentrant);
+            checked.dealloc(ptr, layout);
+            assert_eq!(violations(), [Violation::Reentrant; 4]);
+            assert_eq!(checked.live_blocks(), 1);
+
+            checked.busy = false;
+            checked.dealloc(ptr, layout);
+        }
+        checked.flush();
+        assert_eq!(violations(), []);
+    }
+}
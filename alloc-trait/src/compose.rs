@@ -0,0 +1,525 @@
+use core::{
+    alloc::Layout,
+    cmp,
+    ptr::{self, NonNull},
+};
+
+use crate::{Alloc, AllocError, AllocErrorKind, Excess, Owns};
+
+/// An allocator that never allocates
+///
+/// Useful as the last link of a chain of [`Fallback`] allocators
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullAlloc;
+
+impl Alloc for NullAlloc {
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        Err(AllocError::exhausted(layout))
+    }
+
+    unsafe fn dealloc(&mut self, _ptr: NonNull<u8>, _layout: Layout) {
+        // NOTE this allocator never hands out memory so this is a bug in the caller
+        debug_assert!(false, "`NullAlloc::dealloc` called");
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        _ptr: NonNull<u8>,
+        layout: Layout,
+        _new_size: usize,
+    ) -> Result<(), AllocError> {
+        Err(AllocError::new(layout, AllocErrorKind::NotOwned))
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        _ptr: NonNull<u8>,
+        layout: Layout,
+        _new_size: usize,
+    ) -> Result<(), AllocError> {
+        Err(AllocError::new(layout, AllocErrorKind::NotOwned))
+    }
+}
+
+impl Owns for NullAlloc {
+    fn owns(&self, _ptr: NonNull<u8>, _layout: Layout) -> bool {
+        false
+    }
+}
+
+/// Serves requests from the `primary` allocator and, when that fails, from the `secondary` one
+///
+/// Frees are routed using the `primary`'s [`Owns`] implementation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fallback<P, S> {
+    /// The allocator that's tried first
+    pub primary: P,
+    /// The allocator that's used when `primary` fails
+    pub secondary: S,
+}
+
+impl<P, S> Fallback<P, S> {
+    /// Chains `primary` and `secondary`
+    pub const fn new(primary: P, secondary: S) -> Self {
+        Self { primary, secondary }
+    }
+}
+
+impl<P, S> Alloc for Fallback<P, S>
+where
+    P: Alloc + Owns,
+    S: Alloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.primary
+            .alloc(layout)
+            .or_else(|_| self.secondary.alloc(layout))
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        self.primary
+            .alloc_excess(layout)
+            .or_else(|_| self.secondary.alloc_excess(layout))
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        if self.primary.owns(ptr, layout) {
+            self.primary.dealloc(ptr, layout)
+        } else {
+            self.secondary.dealloc(ptr, layout)
+        }
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        if self.primary.owns(ptr, layout) {
+            self.primary.grow_in_place(ptr, layout, new_size)
+        } else {
+            self.secondary.grow_in_place(ptr, layout, new_size)
+        }
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        if self.primary.owns(ptr, layout) {
+            self.primary.shrink_in_place(ptr, layout, new_size)
+        } else {
+            self.secondary.shrink_in_place(ptr, layout, new_size)
+        }
+    }
+
+    unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        if !self.primary.owns(ptr, layout) {
+            return self.secondary.realloc(ptr, layout, new_size);
+        }
+
+        if let Ok(new_ptr) = self.primary.realloc(ptr, layout, new_size) {
+            return Ok(new_ptr);
+        }
+
+        // the primary couldn't resize the block; move it into the secondary allocator
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = self.secondary.alloc(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr(),
+            cmp::min(layout.size(), new_size),
+        );
+        self.primary.dealloc(ptr, layout);
+        Ok(new_ptr)
+    }
+}
+
+impl<P, S> Owns for Fallback<P, S>
+where
+    P: Owns,
+    S: Owns,
+{
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.primary.owns(ptr, layout) || self.secondary.owns(ptr, layout)
+    }
+}
+
+/// Serves requests of up to `THRESHOLD` bytes from the `small` allocator and bigger requests from
+/// the `large` one
+///
+/// Frees are routed by size so no [`Owns`] implementation is needed
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Segregator<const THRESHOLD: usize, S, L> {
+    /// The allocator that serves requests of up to `THRESHOLD` bytes
+    pub small: S,
+    /// The allocator that serves requests bigger than `THRESHOLD` bytes
+    pub large: L,
+}
+
+impl<const THRESHOLD: usize, S, L> Segregator<THRESHOLD, S, L> {
+    /// Splits requests between `small` and `large`
+    pub const fn new(small: S, large: L) -> Self {
+        Self { small, large }
+    }
+}
+
+impl<const THRESHOLD: usize, S, L> Alloc for Segregator<THRESHOLD, S, L>
+where
+    S: Alloc,
+    L: Alloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() <= THRESHOLD {
+            self.small.alloc(layout)
+        } else {
+            self.large.alloc(layout)
+        }
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() <= THRESHOLD {
+            self.small.dealloc(ptr, layout)
+        } else {
+            self.large.dealloc(ptr, layout)
+        }
+    }
+
+    fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+        if layout.size() <= THRESHOLD {
+            // NOTE the caller must never see a size past the threshold or the block would be
+            // returned to the wrong allocator
+            let (min, max) = self.small.usable_size(layout);
+            (min, cmp::min(max, THRESHOLD))
+        } else {
+            self.large.usable_size(layout)
+        }
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        if layout.size() <= THRESHOLD {
+            self.small
+                .alloc_excess(layout)
+                .map(|Excess(ptr, size)| Excess(ptr, cmp::min(size, THRESHOLD)))
+        } else {
+            self.large.alloc_excess(layout)
+        }
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        match (layout.size() <= THRESHOLD, new_size <= THRESHOLD) {
+            (true, true) => self.small.grow_in_place(ptr, layout, new_size),
+            (false, false) => self.large.grow_in_place(ptr, layout, new_size),
+            _ => Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+                new_size,
+                layout.align(),
+            ))),
+        }
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        match (layout.size() <= THRESHOLD, new_size <= THRESHOLD) {
+            (true, true) => self.small.shrink_in_place(ptr, layout, new_size),
+            (false, false) => self.large.shrink_in_place(ptr, layout, new_size),
+            _ => Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+                new_size,
+                layout.align(),
+            ))),
+        }
+    }
+
+    unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        match (layout.size() <= THRESHOLD, new_size <= THRESHOLD) {
+            (true, true) => self.small.realloc(ptr, layout, new_size),
+            (false, false) => self.large.realloc(ptr, layout, new_size),
+            // the block moves from one allocator to the other
+            _ => {
+                let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+                let new_ptr = self.alloc(new_layout)?;
+                ptr::copy_nonoverlapping(
+                    ptr.as_ptr(),
+                    new_ptr.as_ptr(),
+                    cmp::min(layout.size(), new_size),
+                );
+                self.dealloc(ptr, layout);
+                Ok(new_ptr)
+            }
+        }
+    }
+}
+
+impl<const THRESHOLD: usize, S, L> Owns for Segregator<THRESHOLD, S, L>
+where
+    S: Owns,
+    L: Owns,
+{
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        if layout.size() <= THRESHOLD {
+            self.small.owns(ptr, layout)
+        } else {
+            self.large.owns(ptr, layout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{alloc::Layout, ptr::NonNull};
+    use std::{alloc::System, boxed::Box, vec, vec::Vec};
+
+    use super::{Fallback, NullAlloc, Segregator};
+    use crate::{Alloc, AllocError, AllocErrorKind, Excess, Global, Owns};
+
+    const SLOT: usize = 128;
+
+    #[repr(align(16))]
+    struct Slot([u8; SLOT]);
+
+    /// Hands out `SLOT`-byte blocks from a fixed number of slots
+    struct Slab {
+        slots: &'static mut [Slot],
+        used: Vec<bool>,
+    }
+
+    impl Slab {
+        fn new(n: usize) -> Self {
+            let slots = (0..n).map(|_| Slot([0; SLOT])).collect::<Vec<_>>();
+            Slab {
+                slots: Box::leak(slots.into_boxed_slice()),
+                used: vec![false; n],
+            }
+        }
+
+        fn in_use(&self) -> usize {
+            self.used.iter().filter(|used| **used).count()
+        }
+
+        fn index(&self, ptr: NonNull<u8>) -> Option<usize> {
+            let start = self.slots.as_ptr() as usize;
+            let addr = ptr.as_ptr() as usize;
+            if addr >= start && addr < start + self.slots.len() * SLOT {
+                Some((addr - start) / SLOT)
+            } else {
+                None
+            }
+        }
+    }
+
+    impl Alloc for Slab {
+        unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            if layout.size() > SLOT || layout.align() > 16 {
+                return Err(AllocError::exhausted(layout));
+            }
+
+            let i = self
+                .used
+                .iter()
+                .position(|used| !used)
+                .ok_or(AllocError::exhausted(layout))?;
+            self.used[i] = true;
+            Ok(NonNull::from(&mut self.slots[i].0).cast())
+        }
+
+        unsafe fn dealloc(&mut self, ptr: NonNull<u8>, _layout: Layout) {
+            let i = self.index(ptr).expect("block not allocated by this `Slab`");
+            assert!(self.used[i]);
+            self.used[i] = false;
+        }
+
+        fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+            (layout.size(), SLOT)
+        }
+
+        unsafe fn grow_in_place(
+            &mut self,
+            _ptr: NonNull<u8>,
+            layout: Layout,
+            new_size: usize,
+        ) -> Result<(), AllocError> {
+            if new_size <= SLOT {
+                Ok(())
+            } else {
+                Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+                    new_size,
+                    layout.align(),
+                )))
+            }
+        }
+
+        unsafe fn shrink_in_place(
+            &mut self,
+            _ptr: NonNull<u8>,
+            _layout: Layout,
+            _new_size: usize,
+        ) -> Result<(), AllocError> {
+            Ok(())
+        }
+    }
+
+    impl Owns for Slab {
+        fn owns(&self, ptr: NonNull<u8>, _layout: Layout) -> bool {
+            self.index(ptr).is_some()
+        }
+    }
+
+    unsafe fn fill(ptr: NonNull<u8>, len: usize) {
+        for i in 0..len {
+            ptr.as_ptr().add(i).write(i as u8);
+        }
+    }
+
+    unsafe fn check(ptr: NonNull<u8>, len: usize) {
+        for i in 0..len {
+            assert_eq!(ptr.as_ptr().add(i).read(), i as u8);
+        }
+    }
+
+    #[test]
+    fn null_alloc() {
+        let layout = Layout::new::<u64>();
+        let dangling = NonNull::dangling();
+
+        unsafe {
+            let e = NullAlloc.alloc(layout).unwrap_err();
+            assert_eq!(e.kind(), AllocErrorKind::Exhausted);
+            assert_eq!(e.layout(), layout);
+            assert!(NullAlloc.alloc_excess(layout).is_err());
+
+            let e = NullAlloc.grow_in_place(dangling, layout, 16).unwrap_err();
+            assert_eq!(e.kind(), AllocErrorKind::NotOwned);
+            let e = NullAlloc.shrink_in_place(dangling, layout, 4).unwrap_err();
+            assert_eq!(e.kind(), AllocErrorKind::NotOwned);
+        }
+
+        assert!(!NullAlloc.owns(dangling, layout));
+    }
+
+    #[test]
+    fn fallback() {
+        let mut a = Fallback::new(Slab::new(2), Global(System));
+        let layout = Layout::from_size_align(64, 8).unwrap();
+
+        unsafe {
+            // the third request doesn't fit in the primary
+            let x = a.alloc(layout).unwrap();
+            let y = a.alloc(layout).unwrap();
+            let z = a.alloc(layout).unwrap();
+            assert!(a.primary.owns(x, layout));
+            assert!(a.primary.owns(y, layout));
+            assert!(!a.primary.owns(z, layout));
+            assert_eq!(a.primary.in_use(), 2);
+
+            // frees go to the allocator that owns the block
+            a.dealloc(z, layout);
+            assert_eq!(a.primary.in_use(), 2);
+            a.dealloc(x, layout);
+            assert_eq!(a.primary.in_use(), 1);
+
+            // the primary serves requests again once it has room
+            let w = a.alloc(layout).unwrap();
+            assert!(a.primary.owns(w, layout));
+
+            a.dealloc(w, layout);
+            a.dealloc(y, layout);
+            assert_eq!(a.primary.in_use(), 0);
+        }
+    }
+
+    #[test]
+    fn fallback_realloc() {
+        let mut a = Fallback::new(Slab::new(1), Global(System));
+        let layout = Layout::from_size_align(64, 8).unwrap();
+
+        unsafe {
+            let x = a.alloc(layout).unwrap();
+            fill(x, 64);
+
+            // the block fits in its slot
+            assert_eq!(a.realloc(x, layout, SLOT).unwrap(), x);
+
+            // the block moves into the secondary and its slot is freed
+            let y = a.realloc(x, layout, 2 * SLOT).unwrap();
+            assert!(!a.primary.owns(y, layout));
+            assert_eq!(a.primary.in_use(), 0);
+            check(y, 64);
+
+            a.dealloc(y, Layout::from_size_align(2 * SLOT, 8).unwrap());
+        }
+    }
+
+    #[test]
+    fn segregator() {
+        let mut a = Segregator::<64, _, _>::new(Slab::new(4), Global(System));
+        let at = Layout::from_size_align(64, 8).unwrap();
+        let above = Layout::from_size_align(65, 8).unwrap();
+
+        unsafe {
+            // requests of up to `THRESHOLD` bytes go to the small allocator
+            let x = a.alloc(at).unwrap();
+            assert!(a.small.owns(x, at));
+            let y = a.alloc(above).unwrap();
+            assert!(!a.small.owns(y, above));
+            assert_eq!(a.small.in_use(), 1);
+
+            // frees are routed by size
+            a.dealloc(y, above);
+            assert_eq!(a.small.in_use(), 1);
+            a.dealloc(x, at);
+            assert_eq!(a.small.in_use(), 0);
+
+            // the block moves between allocators when it crosses the threshold
+            let x = a.alloc(Layout::from_size_align(32, 8).unwrap()).unwrap();
+            fill(x, 32);
+            let y = a
+                .realloc(x, Layout::from_size_align(32, 8).unwrap(), 100)
+                .unwrap();
+            assert!(!a.small.owns(y, above));
+            assert_eq!(a.small.in_use(), 0);
+            check(y, 32);
+            a.dealloc(y, Layout::from_size_align(100, 8).unwrap());
+        }
+    }
+
+    #[test]
+    fn segregator_excess() {
+        let mut a = Segregator::<64, _, _>::new(Slab::new(1), Global(System));
+        let layout = Layout::from_size_align(48, 8).unwrap();
+
+        // the small allocator has `SLOT` usable bytes but a larger block would be freed into the
+        // large allocator
+        assert_eq!(a.small.usable_size(&layout), (48, SLOT));
+        assert_eq!(a.usable_size(&layout), (48, 64));
+
+        unsafe {
+            let Excess(ptr, size) = a.alloc_excess(layout).unwrap();
+            assert_eq!(size, 64);
+            a.dealloc(ptr, Layout::from_size_align(size, 8).unwrap());
+            assert_eq!(a.small.in_use(), 0);
+        }
+    }
+}
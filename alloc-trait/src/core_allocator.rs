@@ -0,0 +1,78 @@
+use core::{alloc::Layout, ptr::NonNull};
+
+use crate::{Alloc, Allocator, Handle};
+
+/// Adapter that implements `core::alloc::Allocator` on top of an `Alloc` / [`Allocator`]
+///
+/// This lets the allocators declared with `cortex_m_tm_alloc::allocator` back `alloc`'s
+/// collections, e.g. `alloc::vec::Vec<T, CoreAllocator<A>>`.
+///
+/// `core`'s trait takes `&self` so every operation works on a *copy* of the inner allocator. `A`
+/// must then be a [`Handle`]: all its copies must refer to the *same* allocator state, as is the
+/// case with the handles generated by `cortex_m_tm_alloc::allocator`.
+#[derive(Clone, Copy, Debug)]
+pub struct CoreAllocator<A>(A);
+
+impl<A> CoreAllocator<A>
+where
+    A: Alloc + Handle,
+{
+    /// Wraps the allocator `handle`
+    pub fn new(handle: A) -> Self {
+        CoreAllocator(handle)
+    }
+}
+
+unsafe impl<A> core::alloc::Allocator for CoreAllocator<A>
+where
+    A: Alloc + Handle,
+{
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        let mut a = self.0;
+        a.allocate(layout).map_err(|_| core::alloc::AllocError)
+    }
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        let mut a = self.0;
+        a.allocate_zeroed(layout)
+            .map_err(|_| core::alloc::AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let mut a = self.0;
+        a.deallocate(ptr, layout)
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        let mut a = self.0;
+        a.grow(ptr, old_layout, new_layout)
+            .map_err(|_| core::alloc::AllocError)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        let mut a = self.0;
+        a.grow_zeroed(ptr, old_layout, new_layout)
+            .map_err(|_| core::alloc::AllocError)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+        let mut a = self.0;
+        a.shrink(ptr, old_layout, new_layout)
+            .map_err(|_| core::alloc::AllocError)
+    }
+}
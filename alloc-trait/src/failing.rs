@@ -0,0 +1,268 @@
+use core::{alloc::Layout, ptr::NonNull};
+
+use crate::{Alloc, AllocError, Excess, Owns};
+
+/// Wrapper that makes some of the allocation requests fail, according to a [`Schedule`]
+///
+/// This lets one exercise Out-Of-Memory paths without having to shrink the heap. Allocation
+/// requests are the calls to `alloc`, `alloc_excess`, `realloc` and `realloc_excess`; they are
+/// numbered from 0. Deallocations and in-place resizing are never made to fail.
+pub struct FailingAlloc<A> {
+    inner: A,
+    schedule: Schedule,
+    /// State of the pseudo-random number generator
+    state: u32,
+    requests: usize,
+    failures: usize,
+}
+
+/// Which allocation requests a [`FailingAlloc`] makes fail
+#[derive(Clone, Copy, Debug)]
+pub enum Schedule {
+    /// Never fail
+    Never,
+    /// Fail the `n`-th request, counting from 0
+    Nth(usize),
+    /// Fail every request of more than this many bytes
+    AboveSize(usize),
+    /// Fail the requests for which the predicate returns `true`; the predicate receives the
+    /// layout and the number of the request
+    Predicate(fn(Layout, usize) -> bool),
+    /// Fail, on average, one of every `one_in` requests, chosen by a pseudo-random number
+    /// generator seeded with `seed`; the same seed always fails the same requests
+    Random {
+        /// Seed of the pseudo-random number generator
+        seed: u32,
+        /// Inverse of the failure probability
+        one_in: u32,
+    },
+}
+
+impl<A> FailingAlloc<A> {
+    /// Wraps `inner`; requests will fail according to `schedule`
+    pub const fn new(inner: A, schedule: Schedule) -> Self {
+        Self {
+            inner,
+            state: seed(&schedule),
+            schedule,
+            requests: 0,
+            failures: 0,
+        }
+    }
+
+    /// Changes the schedule and resets the request counter
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.state = seed(&schedule);
+        self.schedule = schedule;
+        self.requests = 0;
+    }
+
+    /// Returns the number of allocation requests received so far
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+
+    /// Returns the number of allocation requests that were made to fail
+    pub fn failures(&self) -> usize {
+        self.failures
+    }
+
+    /// Returns a reference to the inner allocator
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner allocator
+    pub fn inner_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+
+    /// Decides whether the next request must fail
+    fn fail(&mut self, layout: Layout) -> Result<(), AllocError> {
+        let n = self.requests;
+        self.requests += 1;
+
+        let fail = match self.schedule {
+            Schedule::Never => false,
+            Schedule::Nth(nth) => n == nth,
+            Schedule::AboveSize(size) => layout.size() > size,
+            Schedule::Predicate(f) => f(layout, n),
+            Schedule::Random { one_in, .. } => {
+                // xorshift32
+                let mut x = self.state;
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                self.state = x;
+
+                one_in != 0 && x.is_multiple_of(one_in)
+            }
+        };
+
+        if fail {
+            self.failures += 1;
+            Err(AllocError::exhausted(layout))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+const fn seed(schedule: &Schedule) -> u32 {
+    match schedule {
+        // NOTE xorshift gets stuck at zero
+        Schedule::Random { seed: 0, .. } => 1,
+        Schedule::Random { seed, .. } => *seed,
+        _ => 1,
+    }
+}
+
+impl<A> Alloc for FailingAlloc<A>
+where
+    A: Alloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.fail(layout)?;
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+
+    fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+        self.inner.usable_size(layout)
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        self.fail(layout)?;
+        self.inner.alloc_excess(layout)
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        self.inner.grow_in_place(ptr, layout, new_size)
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        self.inner.shrink_in_place(ptr, layout, new_size)
+    }
+
+    unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        self.fail(Layout::from_size_align_unchecked(new_size, layout.align()))?;
+        self.inner.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn realloc_excess(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<Excess, AllocError> {
+        self.fail(Layout::from_size_align_unchecked(new_size, layout.align()))?;
+        self.inner.realloc_excess(ptr, layout, new_size)
+    }
+}
+
+impl<A> Owns for FailingAlloc<A>
+where
+    A: Owns,
+{
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.inner.owns(ptr, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::alloc::Layout;
+    use std::{alloc::System, vec::Vec};
+
+    use super::{FailingAlloc, Schedule};
+    use crate::{Alloc, Global, NullAlloc};
+
+    /// Which of a sequence of requests of the given sizes fail
+    fn failed(schedule: Schedule, sizes: impl IntoIterator<Item = usize>) -> Vec<bool> {
+        let mut a = FailingAlloc::new(NullAlloc, schedule);
+        sizes
+            .into_iter()
+            .map(|size| a.fail(Layout::from_size_align(size, 1).unwrap()).is_err())
+            .collect()
+    }
+
+    #[test]
+    fn nth() {
+        let mut a = FailingAlloc::new(Global(System), Schedule::Nth(1));
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let ptr = a.alloc(layout).unwrap();
+            // `realloc` is an allocation request too
+            assert!(a.realloc(ptr, layout, 16).is_err());
+            let ptr = a.realloc(ptr, layout, 16).unwrap();
+            a.dealloc(ptr, Layout::from_size_align(16, 8).unwrap());
+        }
+
+        assert_eq!((a.requests(), a.failures()), (3, 1));
+    }
+
+    #[test]
+    fn above_size() {
+        assert_eq!(
+            failed(Schedule::AboveSize(16), [8, 16, 17, 64, 0]),
+            [false, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn predicate() {
+        fn odd_or_large(layout: Layout, n: usize) -> bool {
+            n % 2 == 1 || layout.size() > 32
+        }
+
+        assert_eq!(
+            failed(Schedule::Predicate(odd_or_large), [8, 8, 64, 8]),
+            [false, true, true, true]
+        );
+    }
+
+    #[test]
+    fn random() {
+        let random = |seed, one_in| failed(Schedule::Random { seed, one_in }, [8; 10_000]);
+
+        // xorshift32
+        let mut a = FailingAlloc::new(NullAlloc, Schedule::Random { seed: 1, one_in: 2 });
+        a.fail(Layout::new::<u8>()).ok();
+        assert_eq!(a.state, 270_369);
+        a.fail(Layout::new::<u8>()).ok();
+        assert_eq!(a.state, 67_634_689);
+
+        // the same seed fails the same requests
+        assert_eq!(random(42, 4), random(42, 4));
+        assert_ne!(random(42, 4), random(43, 4));
+        // a zero seed would get the generator stuck
+        assert_eq!(random(0, 4), random(1, 4));
+
+        let failures = random(42, 4).iter().filter(|&&fail| fail).count();
+        assert!((2_000..3_000).contains(&failures), "{}", failures);
+
+        assert!(random(42, 1).iter().all(|&fail| fail));
+        assert!(random(42, 0).iter().all(|&fail| !fail));
+    }
+}
@@ -0,0 +1,288 @@
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    ptr::{self, NonNull},
+};
+
+use crate::{allocator::dangling, Alloc, AllocError};
+
+/// Adapter that implements `Alloc` on top of a [`GlobalAlloc`]
+///
+/// For example, `Global(std::alloc::System)` lets the system allocator back the collections in the
+/// `collections` crate.
+///
+/// `GlobalAlloc` has no in-place resizing so `grow_in_place` and `shrink_in_place` always fail;
+/// `realloc` is forwarded to `GlobalAlloc::realloc`. Zero-sized requests never reach the
+/// `GlobalAlloc`, which doesn't support them: they get a dangling, well-aligned, pointer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Global<G>(pub G);
+
+impl<G> Alloc for Global<G>
+where
+    G: GlobalAlloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(dangling(layout).cast());
+        }
+
+        NonNull::new(self.0.alloc(layout)).ok_or(AllocError::exhausted(layout))
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.0.dealloc(ptr.as_ptr(), layout)
+        }
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        _ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+            new_size,
+            layout.align(),
+        )))
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        _ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+            new_size,
+            layout.align(),
+        )))
+    }
+
+    unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            return self.alloc(Layout::from_size_align_unchecked(new_size, layout.align()));
+        }
+
+        if new_size == 0 {
+            self.dealloc(ptr, layout);
+            return Ok(dangling(layout).cast());
+        }
+
+        NonNull::new(self.0.realloc(ptr.as_ptr(), layout, new_size)).ok_or_else(|| {
+            AllocError::exhausted(Layout::from_size_align_unchecked(new_size, layout.align()))
+        })
+    }
+}
+
+/// A mutual exclusion mechanism, like a critical section or a spin lock
+///
+/// # Safety
+///
+/// No two closures passed to `lock` may run at the same time, not even when `lock` is called from
+/// different threads or from interrupt handlers
+pub unsafe trait Lock {
+    /// Runs `f` with exclusive access to the protected data
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R;
+}
+
+/// Adapter that exposes an `Alloc` protected by a [`Lock`] as a [`GlobalAlloc`]
+///
+/// # Example
+///
+/// ```ignore
+/// use alloc_trait::{Lock, Locked};
+/// use tlsf::Tlsf;
+///
+/// struct CriticalSection;
+///
+/// unsafe impl Lock for CriticalSection {
+///     fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+///         cortex_m::interrupt::free(|_| f())
+///     }
+/// }
+///
+/// #[global_allocator]
+/// static HEAP: Locked<Tlsf<4, 8>, CriticalSection> = Locked::new(Tlsf::new(), CriticalSection);
+///
+/// #[entry]
+/// fn main() -> ! {
+///     static mut MEMORY: [u8; 1024] = [0; 1024];
+///
+///     // NOTE nothing else uses `HEAP` at this point
+///     unsafe { HEAP.lock(|tlsf| tlsf.extend(MEMORY)) }.unwrap();
+///
+///     // ..
+/// }
+/// ```
+pub struct Locked<A, L> {
+    alloc: UnsafeCell<A>,
+    lock: L,
+}
+
+impl<A, L> Locked<A, L> {
+    /// Puts `alloc` behind `lock`
+    pub const fn new(alloc: A, lock: L) -> Self {
+        Self {
+            alloc: UnsafeCell::new(alloc),
+            lock,
+        }
+    }
+}
+
+impl<A, L> Locked<A, L>
+where
+    L: Lock,
+{
+    /// Grants `f` exclusive access to the inner allocator
+    ///
+    /// # Safety
+    ///
+    /// `f` must not use this `Locked` allocator, neither directly nor by allocating through
+    /// `#[global_allocator]`: the nested call would create a second `&mut A`. Depending on the
+    /// [`Lock`] implementation the nested call may also deadlock
+    pub unsafe fn lock<R>(&self, f: impl FnOnce(&mut A) -> R) -> R {
+        self.lock.lock(|| f(&mut *self.alloc.get()))
+    }
+}
+
+unsafe impl<A, L> Sync for Locked<A, L>
+where
+    A: Send,
+    L: Sync,
+{
+}
+
+unsafe impl<A, L> GlobalAlloc for Locked<A, L>
+where
+    A: Alloc,
+    L: Lock,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.lock(|a| a.alloc(layout))
+            .map(|p| p.as_ptr())
+            .unwrap_or(ptr::null_mut())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.lock(|a| a.dealloc(NonNull::new_unchecked(ptr), layout))
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.lock(|a| a.realloc(NonNull::new_unchecked(ptr), layout, new_size))
+            .map(|p| p.as_ptr())
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{
+        alloc::{GlobalAlloc, Layout},
+        ptr::NonNull,
+        slice,
+    };
+    use std::{alloc::System, sync::Mutex};
+
+    use super::{Global, Lock, Locked};
+    use crate::Alloc;
+
+    struct StdMutex(Mutex<()>);
+
+    unsafe impl Lock for StdMutex {
+        fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let _guard = self.0.lock().unwrap();
+            f()
+        }
+    }
+
+    unsafe fn fill(ptr: NonNull<u8>, len: usize) {
+        for i in 0..len {
+            ptr.as_ptr().add(i).write(i as u8);
+        }
+    }
+
+    unsafe fn check(ptr: NonNull<u8>, len: usize) {
+        let bytes = slice::from_raw_parts(ptr.as_ptr(), len);
+        assert!(bytes.iter().enumerate().all(|(i, b)| *b == i as u8));
+    }
+
+    #[test]
+    fn global_round_trip() {
+        let mut global = Global(System);
+
+        unsafe {
+            let layout = Layout::from_size_align(24, 8).unwrap();
+            let ptr = global.alloc(layout).unwrap();
+            assert_eq!(ptr.as_ptr() as usize % 8, 0);
+            fill(ptr, 24);
+
+            let ptr = global.realloc(ptr, layout, 100).unwrap();
+            check(ptr, 24);
+            fill(ptr, 100);
+
+            let layout = Layout::from_size_align(100, 8).unwrap();
+            let ptr = global.realloc(ptr, layout, 10).unwrap();
+            check(ptr, 10);
+
+            global.dealloc(ptr, Layout::from_size_align(10, 8).unwrap());
+        }
+    }
+
+    #[test]
+    fn global_zero_size() {
+        let mut global = Global(System);
+
+        unsafe {
+            let layout = Layout::from_size_align(0, 64).unwrap();
+            let ptr = global.alloc(layout).unwrap();
+            assert_eq!(ptr.as_ptr() as usize % 64, 0);
+            global.dealloc(ptr, layout);
+
+            // from and to zero-sized blocks
+            let ptr = global.alloc(layout).unwrap();
+            let ptr = global.realloc(ptr, layout, 32).unwrap();
+            assert_eq!(ptr.as_ptr() as usize % 64, 0);
+            fill(ptr, 32);
+
+            let ptr = global
+                .realloc(ptr, Layout::from_size_align(32, 64).unwrap(), 0)
+                .unwrap();
+            assert_eq!(ptr.as_ptr() as usize % 64, 0);
+            global.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn locked_round_trip() {
+        let locked = Locked::new(Global(System), StdMutex(Mutex::new(())));
+
+        unsafe {
+            let layout = Layout::from_size_align(24, 8).unwrap();
+            let ptr = NonNull::new(locked.alloc(layout)).unwrap();
+            fill(ptr, 24);
+
+            let ptr = NonNull::new(locked.realloc(ptr.as_ptr(), layout, 100)).unwrap();
+            check(ptr, 24);
+            locked.dealloc(ptr.as_ptr(), Layout::from_size_align(100, 8).unwrap());
+
+            let ptr = NonNull::new(locked.alloc_zeroed(layout)).unwrap();
+            let bytes = slice::from_raw_parts(ptr.as_ptr(), 24);
+            assert!(bytes.iter().all(|b| *b == 0));
+            locked.dealloc(ptr.as_ptr(), layout);
+
+            // zero-sized requests through the inner allocator
+            let layout = Layout::from_size_align(0, 16).unwrap();
+            let ptr = locked.lock(|global| global.alloc(layout)).unwrap();
+            assert_eq!(ptr.as_ptr() as usize % 16, 0);
+            locked.lock(|global| global.dealloc(ptr, layout));
+        }
+    }
+}
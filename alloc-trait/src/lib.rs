@@ -1,68 +1,299 @@
 //! `core::alloc::Alloc` on stable
+//!
+//! Also provides [`Allocator`], a stable take on the newer `core::alloc::Allocator` API, and
+//! adapters between `Alloc` and `core::alloc::GlobalAlloc`: [`Global`] and [`Locked`]
+//!
+//! Allocators can be composed using [`Fallback`], [`Segregator`] and [`NullAlloc`]; [`Stats`]
+//! keeps track of how an allocator is used, [`Checked`] detects misuse of an allocator and
+//! [`FailingAlloc`] injects allocation failures
+//!
+//! # Cargo features
+//!
+//! - `allocator-api` (nightly only): provides `CoreAllocator`, an adapter that implements
+//!   `core::alloc::Allocator` for `Alloc` [`Handle`]s
 
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 #![deny(missing_docs)]
 #![deny(warnings)]
 #![no_std]
 
 use core::{
     alloc::Layout,
-    cmp,
+    cmp, fmt,
     ptr::{self, NonNull},
 };
 
+pub use crate::allocator::Allocator;
+pub use crate::checked::{Checked, Violation};
+pub use crate::compose::{Fallback, NullAlloc, Segregator};
+#[cfg(feature = "allocator-api")]
+pub use crate::core_allocator::CoreAllocator;
+pub use crate::failing::{FailingAlloc, Schedule};
+pub use crate::global::{Global, Lock, Locked};
+pub use crate::oom::{Decision, OomHandler, OomInfo, Operation};
+pub use crate::stats::{Snapshot, Stats};
+
+mod allocator;
+mod checked;
+mod compose;
+#[cfg(feature = "allocator-api")]
+mod core_allocator;
+mod failing;
+mod global;
+mod oom;
+mod stats;
+
+/// The error returned by a failed [`Alloc`] operation
+#[derive(Clone, Copy, Debug, Eq)]
+pub struct AllocError {
+    layout: Layout,
+    kind: AllocErrorKind,
+    usage: Option<Usage>,
+    allocator: Option<&'static str>,
+    handler: Option<OomHandler>,
+}
+
+impl AllocError {
+    /// Creates a new error for a request of the given `layout`
+    pub const fn new(layout: Layout, kind: AllocErrorKind) -> Self {
+        Self {
+            layout,
+            kind,
+            usage: None,
+            allocator: None,
+            handler: None,
+        }
+    }
+
+    /// Shorthand for `AllocError::new(layout, AllocErrorKind::Exhausted)`
+    pub const fn exhausted(layout: Layout) -> Self {
+        Self::new(layout, AllocErrorKind::Exhausted)
+    }
+
+    /// Attaches the allocator's usage figures, as observed when the request failed
+    pub const fn with_usage(mut self, usage: Usage) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Attaches the name of the allocator that failed to serve the request
+    pub const fn with_allocator(mut self, name: &'static str) -> Self {
+        self.allocator = Some(name);
+        self
+    }
+
+    /// Attaches the Out-Of-Memory handler dedicated to the allocator that failed
+    pub const fn with_handler(mut self, handler: OomHandler) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    /// The layout of the request that failed
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Why the request failed
+    pub fn kind(&self) -> AllocErrorKind {
+        self.kind
+    }
+
+    /// The allocator's usage figures, if the allocator reported them
+    pub fn usage(&self) -> Option<Usage> {
+        self.usage
+    }
+
+    /// The name of the allocator that failed, if known
+    pub fn allocator(&self) -> Option<&'static str> {
+        self.allocator
+    }
+
+    /// The Out-Of-Memory handler dedicated to the allocator that failed, if any
+    pub fn handler(&self) -> Option<OomHandler> {
+        self.handler
+    }
+}
+
+// NOTE the attached handler is not compared: function pointers have no meaningful identity
+impl PartialEq for AllocError {
+    fn eq(&self, other: &Self) -> bool {
+        self.layout == other.layout
+            && self.kind == other.kind
+            && self.usage == other.usage
+            && self.allocator == other.allocator
+    }
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+
+        if let Some(name) = self.allocator {
+            write!(f, " in `{}`", name)?;
+        }
+
+        write!(
+            f,
+            " (size: {}, align: {})",
+            self.layout.size(),
+            self.layout.align()
+        )?;
+
+        if let Some(usage) = self.usage {
+            write!(
+                f,
+                "; {} bytes free, {} bytes used, largest free block: {} bytes",
+                usage.free, usage.used, usage.largest_free_block
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The reason behind an [`AllocError`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AllocErrorKind {
+    /// The allocator doesn't have a free block large enough to satisfy the request
+    Exhausted,
+    /// The requested alignment is larger than what the allocator can provide
+    UnsupportedAlignment,
+    /// The requested size, after adding the allocator's overhead, overflows `usize`
+    SizeOverflow,
+    /// The pointer passed to the allocator was not allocated by it
+    NotOwned,
+    /// The allocator was called into while another operation was in progress
+    Reentrant,
+}
+
+impl fmt::Display for AllocErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AllocErrorKind::Exhausted => "memory exhausted",
+            AllocErrorKind::UnsupportedAlignment => "unsupported alignment",
+            AllocErrorKind::SizeOverflow => "size overflow",
+            AllocErrorKind::NotOwned => "pointer not owned by this allocator",
+            AllocErrorKind::Reentrant => "reentrant call",
+        })
+    }
+}
+
+/// Free memory figures an allocator can attach to an [`AllocError`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Usage {
+    /// Total number of free bytes
+    pub free: usize,
+    /// Total number of bytes in use, including the allocator's overhead
+    pub used: usize,
+    /// Size, in bytes, of the largest free block
+    pub largest_free_block: usize,
+}
+
+/// See [`core::alloc::Excess`][0]
+///
+/// [0]: https://doc.rust-lang.org/core/alloc/struct.Excess.html
+#[derive(Clone, Copy, Debug)]
+pub struct Excess(pub NonNull<u8>, pub usize);
+
 /// See [`core::alloc::Alloc`][0]
 ///
+/// The terms *currently allocated* and *fits* have the same meaning as in [`Allocator`]; the
+/// block returned by `alloc` is as large as the requested size and the one returned by
+/// `alloc_excess` (or `realloc_excess`) is as large as the returned `Excess` size.
+///
 /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html
 pub trait Alloc {
     /// See [`core::alloc::Alloc.alloc`][0]
     ///
     /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#tymethod.alloc
-    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, ()>;
+    ///
+    /// # Safety
+    ///
+    /// `layout` must have a non-zero size
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
 
     /// See [`core::alloc::Alloc.dealloc`][0]
     ///
     /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#tymethod.dealloc
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block currently allocated by this allocator and `layout` must fit that
+    /// block
     unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: core::alloc::Layout);
 
+    /// See [`core::alloc::Alloc.usable_size`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#method.usable_size
+    fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+        (layout.size(), layout.size())
+    }
+
+    /// See [`core::alloc::Alloc.alloc_excess`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#method.alloc_excess
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Alloc::alloc`]
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        let usable_size = self.usable_size(&layout);
+        self.alloc(layout).map(|p| Excess(p, usable_size.1))
+    }
+
     /// See [`core::alloc::Alloc.grow_in_place`][0]
     ///
     /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#tymethod.grow_in_place
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block currently allocated by this allocator, `layout` must fit that
+    /// block and `new_size` must be greater than or equal to `layout.size()`
     unsafe fn grow_in_place(
         &mut self,
         ptr: NonNull<u8>,
         layout: Layout,
         new_size: usize,
-    ) -> Result<(), ()>;
+    ) -> Result<(), AllocError>;
 
     /// See [`core::alloc::Alloc.shrink_in_place`][0]
     ///
     /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#tymethod.shrink_in_place
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block currently allocated by this allocator, `layout` must fit that
+    /// block and `new_size` must be non-zero and smaller than or equal to `layout.size()`
     unsafe fn shrink_in_place(
         &mut self,
         ptr: NonNull<u8>,
         layout: Layout,
         new_size: usize,
-    ) -> Result<(), ()>;
+    ) -> Result<(), AllocError>;
 
     /// See [`core::alloc::Alloc.realloc`][0]
     ///
     /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#tymethod.realloc
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must denote a block currently allocated by this allocator, `layout` must fit that
+    /// block and `new_size` must be non-zero. On success the block at `ptr` is no longer
+    /// allocated, even if the returned block has the same address
     unsafe fn realloc(
         &mut self,
         ptr: NonNull<u8>,
         layout: Layout,
         new_size: usize,
-    ) -> Result<NonNull<u8>, ()> {
+    ) -> Result<NonNull<u8>, AllocError> {
         let old_size = layout.size();
 
         if new_size >= old_size {
             if self.grow_in_place(ptr, layout, new_size).is_ok() {
                 return Ok(ptr);
             }
-        } else if new_size < old_size {
-            if self.shrink_in_place(ptr, layout, new_size).is_ok() {
-                return Ok(ptr);
-            }
+        } else if self.shrink_in_place(ptr, layout, new_size).is_ok() {
+            return Ok(ptr);
         }
 
         // otherwise, fall back on alloc + copy + dealloc.
@@ -74,4 +305,43 @@ pub trait Alloc {
         }
         result
     }
+
+    /// See [`core::alloc::Alloc.realloc_excess`][0]
+    ///
+    /// [0]: https://doc.rust-lang.org/core/alloc/trait.Alloc.html#method.realloc_excess
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Alloc::realloc`]
+    unsafe fn realloc_excess(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<Excess, AllocError> {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let usable_size = self.usable_size(&new_layout);
+        self.realloc(ptr, layout, new_size)
+            .map(|p| Excess(p, usable_size.1))
+    }
+}
+
+/// Allocators that can tell whether a block belongs to them
+///
+/// Required by [`Fallback`] to route frees to the allocator that served the request
+pub trait Owns {
+    /// Returns `true` if the block at `ptr`, with the given `layout`, was allocated by this
+    /// allocator
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool;
 }
+
+/// Allocator handles: cheap `Copy` values that all refer to the same allocator state
+///
+/// The handles generated by `cortex_m_tm_alloc::allocator` implement this trait.
+///
+/// # Safety
+///
+/// All the copies of a handle must be the same allocator: a block allocated through one copy can
+/// be freed, or reallocated, through any other copy. Allocators that store their state by value
+/// must not implement this trait.
+pub unsafe trait Handle: Copy {}
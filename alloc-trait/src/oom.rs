@@ -0,0 +1,98 @@
+use core::{alloc::Layout, fmt};
+
+use crate::AllocError;
+
+/// An Out-Of-Memory handler
+///
+/// See `alloc_oom::oom` and `cortex_m_tm_alloc::allocator`
+pub type OomHandler = fn(OomInfo) -> Decision;
+
+/// What to do about a failed allocation request, as decided by the Out-Of-Memory handler
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Decision {
+    /// Retry the request, e.g. because the handler freed some memory
+    Retry,
+    /// Report the error to the caller; callers that can't report errors panic
+    Fail,
+}
+
+/// What was being done when memory ran out
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operation {
+    /// Allocating a new block
+    Alloc,
+    /// Resizing an existing block
+    Realloc,
+    /// Adding a task to an executor whose task queue is full
+    QueueFull,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Alloc => "alloc",
+            Operation::Realloc => "realloc",
+            Operation::QueueFull => "executor queue full",
+        })
+    }
+}
+
+/// Context passed to the Out-Of-Memory handler
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OomInfo {
+    error: AllocError,
+    operation: Operation,
+}
+
+impl OomInfo {
+    /// Describes a failed `operation`
+    pub const fn new(error: AllocError, operation: Operation) -> Self {
+        Self { error, operation }
+    }
+
+    /// The error reported by the allocator
+    pub fn error(&self) -> AllocError {
+        self.error
+    }
+
+    /// The layout of the request that failed
+    pub fn layout(&self) -> Layout {
+        self.error.layout()
+    }
+
+    /// The operation that failed
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// The name of the allocator that failed, if known
+    ///
+    /// Allocators declared with `cortex_m_tm_alloc::allocator` report the name of their `static`
+    /// variable
+    pub fn allocator(&self) -> Option<&'static str> {
+        self.error.allocator()
+    }
+
+    /// Number of free bytes, if the allocator reported it
+    pub fn free(&self) -> Option<usize> {
+        self.error.usage().map(|usage| usage.free)
+    }
+
+    /// Number of used bytes, if the allocator reported it
+    pub fn used(&self) -> Option<usize> {
+        self.error.usage().map(|usage| usage.used)
+    }
+}
+
+/// The error of a failed `alloc` operation
+impl From<AllocError> for OomInfo {
+    fn from(error: AllocError) -> Self {
+        Self::new(error, Operation::Alloc)
+    }
+}
+
+impl fmt::Display for OomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.operation, self.error)
+    }
+}
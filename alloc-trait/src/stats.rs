@@ -0,0 +1,325 @@
+use core::{alloc::Layout, ptr::NonNull};
+
+use crate::{Alloc, AllocError, Excess, Owns};
+
+/// Wrapper that keeps statistics about the use of the inner allocator
+///
+/// # Example
+///
+/// ```ignore
+/// use alloc_trait::Stats;
+/// use cortex_m_tm_alloc::allocator;
+/// use tlsf::Tlsf;
+///
+/// #[allocator(lazy)]
+/// static mut A: Stats<Tlsf<4, 8>> = {
+///     static mut MEMORY: [u8; 1024] = [0; 1024];
+///
+///     let mut tlsf = Tlsf::new();
+///     tlsf.extend(MEMORY).unwrap();
+///     Stats::new(tlsf)
+/// };
+///
+/// #[entry]
+/// fn main() -> ! {
+///     if let Some(a) = A::get() {
+///         // .. use `a` ..
+///
+///         let snapshot = unsafe { a.with(|stats| stats.snapshot()) };
+///         hprintln!("high-water mark: {} bytes", snapshot.peak).ok();
+///     }
+///
+///     // ..
+/// }
+/// ```
+pub struct Stats<A> {
+    inner: A,
+    snapshot: Snapshot,
+}
+
+/// Statistics collected by a [`Stats`] allocator
+///
+/// Byte counts are the sizes of the blocks handed out to the callers: the requested size or, for
+/// `alloc_excess` and `realloc_excess`, the usable size reported by the inner allocator. They
+/// don't include the inner allocator's bookkeeping overhead
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Snapshot {
+    /// Number of successful allocations
+    pub allocs: usize,
+    /// Number of deallocations
+    pub deallocs: usize,
+    /// Number of successful reallocations, including in-place ones
+    pub reallocs: usize,
+    /// Number of requests that the inner allocator couldn't satisfy
+    pub failures: usize,
+    /// Number of bytes currently allocated
+    pub in_use: usize,
+    /// Highest value `in_use` has reached (AKA the high-water mark)
+    pub peak: usize,
+}
+
+impl<A> Stats<A> {
+    /// Starts collecting statistics about `inner`
+    pub const fn new(inner: A) -> Self {
+        Self {
+            inner,
+            snapshot: Snapshot {
+                allocs: 0,
+                deallocs: 0,
+                reallocs: 0,
+                failures: 0,
+                in_use: 0,
+                peak: 0,
+            },
+        }
+    }
+
+    /// Returns the statistics collected so far
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshot
+    }
+
+    /// Clears the counters
+    ///
+    /// `in_use` is kept as the memory is still allocated; `peak` restarts from `in_use`
+    pub fn reset(&mut self) {
+        let in_use = self.snapshot.in_use;
+        self.snapshot = Snapshot {
+            in_use,
+            peak: in_use,
+            ..Snapshot::default()
+        };
+    }
+
+    /// Returns a reference to the inner allocator
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner allocator
+    ///
+    /// Operations performed directly on the inner allocator are not accounted for
+    pub fn inner_mut(&mut self) -> &mut A {
+        &mut self.inner
+    }
+
+    /// Returns the inner allocator
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    fn record<T>(
+        &mut self,
+        res: Result<T, AllocError>,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<T, AllocError> {
+        let snapshot = &mut self.snapshot;
+        if res.is_ok() {
+            snapshot.in_use = snapshot.in_use - old_size + new_size;
+            if snapshot.in_use > snapshot.peak {
+                snapshot.peak = snapshot.in_use;
+            }
+        } else {
+            snapshot.failures += 1;
+        }
+        res
+    }
+}
+
+impl<A> Alloc for Stats<A>
+where
+    A: Alloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let res = self.inner.alloc(layout);
+        if res.is_ok() {
+            self.snapshot.allocs += 1;
+        }
+        self.record(res, 0, layout.size())
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        self.snapshot.deallocs += 1;
+        self.snapshot.in_use -= layout.size();
+    }
+
+    fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+        self.inner.usable_size(layout)
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        let res = self.inner.alloc_excess(layout);
+        // callers may use, and later free, the whole block
+        let size = match res {
+            Ok(Excess(_, size)) => {
+                self.snapshot.allocs += 1;
+                size
+            }
+            Err(_) => layout.size(),
+        };
+        self.record(res, 0, size)
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let res = self.inner.grow_in_place(ptr, layout, new_size);
+        if res.is_ok() {
+            self.snapshot.reallocs += 1;
+        }
+        self.record(res, layout.size(), new_size)
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let res = self.inner.shrink_in_place(ptr, layout, new_size);
+        if res.is_ok() {
+            self.snapshot.reallocs += 1;
+        }
+        self.record(res, layout.size(), new_size)
+    }
+
+    unsafe fn realloc(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let res = self.inner.realloc(ptr, layout, new_size);
+        if res.is_ok() {
+            self.snapshot.reallocs += 1;
+        }
+        self.record(res, layout.size(), new_size)
+    }
+
+    unsafe fn realloc_excess(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<Excess, AllocError> {
+        let res = self.inner.realloc_excess(ptr, layout, new_size);
+        let new_size = match res {
+            Ok(Excess(_, size)) => {
+                self.snapshot.reallocs += 1;
+                size
+            }
+            Err(_) => new_size,
+        };
+        self.record(res, layout.size(), new_size)
+    }
+}
+
+impl<A> Owns for Stats<A>
+where
+    A: Owns,
+{
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.inner.owns(ptr, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{alloc::Layout, ptr::NonNull};
+    use std::alloc::System;
+
+    use super::Stats;
+    use crate::{Alloc, AllocError, Allocator, Global};
+
+    // hands out blocks rounded up to a multiple of 16 bytes
+    struct Rounding(Global<System>);
+
+    fn round(layout: Layout) -> Layout {
+        Layout::from_size_align((layout.size() + 15) & !15, layout.align()).unwrap()
+    }
+
+    impl Alloc for Rounding {
+        unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            self.0.alloc(round(layout))
+        }
+
+        unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.dealloc(ptr, round(layout))
+        }
+
+        fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+            (layout.size(), round(*layout).size())
+        }
+
+        unsafe fn grow_in_place(
+            &mut self,
+            _: NonNull<u8>,
+            layout: Layout,
+            _: usize,
+        ) -> Result<(), AllocError> {
+            Err(AllocError::exhausted(layout))
+        }
+
+        unsafe fn shrink_in_place(
+            &mut self,
+            _: NonNull<u8>,
+            layout: Layout,
+            _: usize,
+        ) -> Result<(), AllocError> {
+            Err(AllocError::exhausted(layout))
+        }
+
+        unsafe fn realloc(
+            &mut self,
+            ptr: NonNull<u8>,
+            layout: Layout,
+            new_size: usize,
+        ) -> Result<NonNull<u8>, AllocError> {
+            let new_layout = round(Layout::from_size_align(new_size, layout.align()).unwrap());
+            self.0.realloc(ptr, round(layout), new_layout.size())
+        }
+    }
+
+    #[test]
+    fn excess() {
+        let mut stats = Stats::new(Rounding(Global(System)));
+
+        // like `collections::Vec`: the capacity, and thus the layout used to grow and free the
+        // buffer, is the length of the block handed out by the allocator
+        unsafe {
+            let block = stats.allocate(Layout::new::<u8>()).unwrap();
+            assert_eq!(block.len(), 16);
+            assert_eq!(stats.snapshot().in_use, 16);
+
+            let old_layout = Layout::from_size_align(block.len(), 1).unwrap();
+            let block = stats
+                .grow(
+                    block.cast(),
+                    old_layout,
+                    Layout::from_size_align(17, 1).unwrap(),
+                )
+                .unwrap();
+            assert_eq!(block.len(), 32);
+            assert_eq!(stats.snapshot().in_use, 32);
+
+            stats.deallocate(
+                block.cast(),
+                Layout::from_size_align(block.len(), 1).unwrap(),
+            );
+        }
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.allocs, 1);
+        assert_eq!(snapshot.reallocs, 1);
+        assert_eq!(snapshot.deallocs, 1);
+        assert_eq!(snapshot.in_use, 0);
+        assert_eq!(snapshot.peak, 32);
+    }
+}
@@ -0,0 +1,285 @@
+//! Bump (arena) allocator
+//!
+//! Allocating is bumping a pointer; memory is reclaimed in bulk using checkpoints or by resetting
+//! the whole arena. This is a good fit for one-shot setup allocations that are never freed
+//! individually, like the ones done by tasks spawned on `cortex_m_tm_executor`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use arena::Arena;
+//! use cortex_m_tm_alloc::allocator;
+//!
+//! #[allocator(lazy)]
+//! static mut A: Arena = {
+//!     static mut MEMORY: [u8; 256] = [0; 256];
+//!
+//!     Arena::new(MEMORY)
+//! };
+//! ```
+
+#![deny(missing_docs)]
+#![deny(warnings)]
+#![no_std]
+
+use core::{alloc::Layout, ptr::NonNull};
+
+use alloc_trait::{Alloc, AllocError, AllocErrorKind, Owns, Usage};
+
+/// Bump allocator over a static buffer
+///
+/// `dealloc` only reclaims memory when the freed block is the last one that was allocated; use
+/// [`Arena::checkpoint`] and [`Arena::rollback`], or [`Arena::reset`], to reclaim memory in bulk
+pub struct Arena {
+    start: usize,
+    end: usize,
+    /// Address of the first free byte
+    top: usize,
+    /// Address of the last allocated block
+    last: Option<usize>,
+}
+
+/// The state of an [`Arena`] at some point in time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Checkpoint {
+    top: usize,
+}
+
+impl Arena {
+    /// Creates an arena that manages the given `memory`
+    pub fn new(memory: &'static mut [u8]) -> Self {
+        let start = memory.as_mut_ptr() as usize;
+        Self {
+            start,
+            end: start + memory.len(),
+            top: start,
+            last: None,
+        }
+    }
+
+    /// Returns the number of bytes currently in use, including alignment padding
+    pub fn used(&self) -> usize {
+        self.top - self.start
+    }
+
+    /// Returns the number of bytes that have not been handed out yet
+    pub fn remaining(&self) -> usize {
+        self.end - self.top
+    }
+
+    /// Records the current state of the arena
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { top: self.top }
+    }
+
+    /// Frees all the blocks allocated after `checkpoint` was taken
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `checkpoint` lies past the memory currently in use, e.g. after
+    /// rolling back to an earlier checkpoint. That's the only check: once the arena has grown past
+    /// a stale checkpoint again, rolling back to it is not detected
+    ///
+    /// # Safety
+    ///
+    /// `checkpoint` must have been taken from this arena, and not invalidated since by rolling
+    /// back to an earlier checkpoint or by calling [`Arena::reset`]. The blocks allocated after
+    /// `checkpoint` was taken must not be used after this call
+    pub unsafe fn rollback(&mut self, checkpoint: Checkpoint) {
+        assert!(
+            checkpoint.top >= self.start && checkpoint.top <= self.top,
+            "invalid checkpoint"
+        );
+
+        self.top = checkpoint.top;
+        self.last = None;
+    }
+
+    /// Frees all the blocks
+    ///
+    /// # Safety
+    ///
+    /// None of the blocks allocated so far may be used after this call
+    pub unsafe fn reset(&mut self) {
+        self.top = self.start;
+        self.last = None;
+    }
+
+    fn usage(&self) -> Usage {
+        Usage {
+            free: self.remaining(),
+            used: self.used(),
+            largest_free_block: self.remaining(),
+        }
+    }
+}
+
+impl Alloc for Arena {
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        let align_mask = layout.align() - 1;
+        let start = self
+            .top
+            .checked_add(align_mask)
+            .map(|top| top & !align_mask)
+            .ok_or_else(|| AllocError::new(layout, AllocErrorKind::SizeOverflow))?;
+        let end = start
+            .checked_add(layout.size())
+            .ok_or_else(|| AllocError::new(layout, AllocErrorKind::SizeOverflow))?;
+
+        if end > self.end {
+            return Err(AllocError::exhausted(layout).with_usage(self.usage()));
+        }
+
+        self.top = end;
+        self.last = Some(start);
+        Ok(NonNull::new_unchecked(start as *mut u8))
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, _layout: Layout) {
+        if self.last == Some(ptr.as_ptr() as usize) {
+            // NOTE the alignment padding before this block is not reclaimed
+            self.top = ptr.as_ptr() as usize;
+            self.last = None;
+        }
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let start = ptr.as_ptr() as usize;
+
+        if self.last != Some(start) {
+            return Err(AllocError::exhausted(new_layout));
+        }
+
+        match start.checked_add(new_size) {
+            Some(end) if end <= self.end => {
+                self.top = end;
+                Ok(())
+            }
+            _ => Err(AllocError::exhausted(new_layout).with_usage(self.usage())),
+        }
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        _layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let start = ptr.as_ptr() as usize;
+
+        // any other block simply keeps its original size
+        if self.last == Some(start) {
+            self.top = start + new_size;
+        }
+
+        Ok(())
+    }
+}
+
+impl Owns for Arena {
+    fn owns(&self, ptr: NonNull<u8>, _layout: Layout) -> bool {
+        let addr = ptr.as_ptr() as usize;
+        addr >= self.start && addr < self.top
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::alloc::Layout;
+    use std::{boxed::Box, vec};
+
+    use alloc_trait::Alloc;
+
+    use super::Arena;
+
+    fn arena(len: usize) -> Arena {
+        Arena::new(Box::leak(vec![0; len].into_boxed_slice()))
+    }
+
+    #[test]
+    fn checkpoint_rollback() {
+        let mut arena = arena(256);
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        unsafe {
+            let a = arena.alloc(layout).unwrap();
+            let used = arena.used();
+            let checkpoint = arena.checkpoint();
+
+            let b = arena.alloc(layout).unwrap();
+            let c = arena.alloc(layout).unwrap();
+            assert!(b.as_ptr() > a.as_ptr() && c.as_ptr() > b.as_ptr());
+            assert!(arena.used() >= used + 32);
+
+            arena.rollback(checkpoint);
+            assert_eq!(arena.used(), used);
+            assert_eq!(arena.checkpoint(), checkpoint);
+
+            // the memory handed out after the checkpoint is reused
+            assert_eq!(arena.alloc(layout).unwrap(), b);
+        }
+    }
+
+    #[test]
+    fn nested_checkpoints() {
+        let mut arena = arena(256);
+        let layout = Layout::new::<u32>();
+
+        unsafe {
+            let outer = arena.checkpoint();
+            arena.alloc(layout).unwrap();
+            let inner = arena.checkpoint();
+            arena.alloc(layout).unwrap();
+
+            arena.rollback(inner);
+            arena.rollback(outer);
+            assert_eq!(arena.used(), 0);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid checkpoint")]
+    fn stale_checkpoint() {
+        let mut arena = arena(256);
+        let layout = Layout::new::<u32>();
+
+        unsafe {
+            let outer = arena.checkpoint();
+            arena.alloc(layout).unwrap();
+            let inner = arena.checkpoint();
+
+            arena.rollback(outer);
+            arena.rollback(inner);
+        }
+    }
+
+    #[test]
+    fn reset_and_last_block() {
+        let mut arena = arena(64);
+        let layout = Layout::from_size_align(32, 1).unwrap();
+
+        unsafe {
+            let a = arena.alloc(layout).unwrap();
+            let b = arena.alloc(layout).unwrap();
+            assert!(arena.alloc(Layout::new::<u8>()).is_err());
+
+            // only the last block is reclaimed by `dealloc`
+            arena.dealloc(a, layout);
+            assert_eq!(arena.remaining(), 0);
+            arena.dealloc(b, layout);
+            assert_eq!(arena.remaining(), 32);
+
+            arena.reset();
+            assert_eq!(arena.remaining(), 64);
+            assert_eq!(arena.alloc(layout).unwrap(), a);
+        }
+    }
+}
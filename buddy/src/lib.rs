@@ -0,0 +1,448 @@
+//! Binary buddy allocator
+//!
+//! Memory is handed out in power-of-two blocks. A free block is split in two halves ("buddies")
+//! until it matches the request; on `dealloc` a block is merged with its buddy, if free, to
+//! rebuild the larger block. Blocks are naturally aligned to their size (relative to the start of
+//! the heap) which makes this allocator a good fit for power-of-two sized DMA buffers.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use buddy::Buddy;
+//! use cortex_m_tm_alloc::allocator;
+//!
+//! #[allocator(lazy)]
+//! static mut A: Buddy = {
+//!     static mut MEMORY: [u8; 1024] = [0; 1024];
+//!
+//!     // the smallest block is `1 << 4` = 16 bytes
+//!     Buddy::new(MEMORY)
+//! };
+//! ```
+//!
+//! # Comparison with TLSF
+//!
+//! Buddy blocks have no header and are aligned to their size so power-of-two buffers aligned to
+//! their size use the heap without waste. `tlsf::Tlsf` puts a header in front of every block and
+//! turns the padding needed to reach a large alignment into a free block, which wastes a large
+//! part of the heap on such buffers. Sizes that are not a power of two favor TLSF, as the buddy
+//! allocator rounds them up to the next power of two.
+
+#![deny(missing_docs)]
+#![deny(warnings)]
+#![no_std]
+
+use core::{alloc::Layout, cmp, mem, ptr::NonNull};
+
+use alloc_trait::{Alloc, AllocError, AllocErrorKind, Excess, Owns, Usage};
+
+const ORDERS: usize = mem::size_of::<usize>() * 8;
+
+/// Binary buddy allocator
+///
+/// The smallest block has a size of `1 << MIN_ORDER` bytes
+pub struct Buddy<const MIN_ORDER: usize = 4> {
+    /// Start of the heap, aligned to `1 << MIN_ORDER`
+    base: usize,
+    len: usize,
+    /// Free lists, indexed by order
+    free: [Option<NonNull<Free>>; ORDERS],
+}
+
+struct Free {
+    next: Option<NonNull<Free>>,
+}
+
+// NOTE `Buddy` owns the memory it manages (`&'static mut [u8]`)
+unsafe impl<const MIN_ORDER: usize> Send for Buddy<MIN_ORDER> {}
+
+impl<const MIN_ORDER: usize> Buddy<MIN_ORDER> {
+    /// Creates a buddy allocator that manages the given `memory`
+    ///
+    /// # Panics
+    ///
+    /// This constructor panics if the minimum block can't hold a pointer
+    pub fn new(memory: &'static mut [u8]) -> Self {
+        assert!(
+            1 << MIN_ORDER >= mem::size_of::<Free>(),
+            "`MIN_ORDER` is too small"
+        );
+
+        let start = memory.as_mut_ptr() as usize;
+        let end = start + memory.len();
+        let min = 1 << MIN_ORDER;
+        let base = cmp::min((start + min - 1) & !(min - 1), end);
+        // NOTE the tail that doesn't fit in a minimum block is not used
+        let len = (end - base) & !(min - 1);
+
+        let mut buddy = Self {
+            base,
+            len,
+            free: [None; ORDERS],
+        };
+
+        // split the heap into the largest possible naturally aligned blocks
+        let mut offset = 0;
+        while offset < len {
+            let max_order = if offset == 0 {
+                ORDERS - 1
+            } else {
+                offset.trailing_zeros() as usize
+            };
+            let fit_order = (usize::BITS - 1 - (len - offset).leading_zeros()) as usize;
+            let order = cmp::min(max_order, fit_order);
+
+            unsafe { buddy.push(order, base + offset) }
+            offset += 1 << order;
+        }
+
+        buddy
+    }
+
+    /// Returns the number of free bytes
+    pub fn free(&self) -> usize {
+        (MIN_ORDER..ORDERS)
+            .map(|order| self.iter(order).count() << order)
+            .sum()
+    }
+
+    /// Returns the size of the largest free block
+    pub fn largest_free_block(&self) -> usize {
+        (MIN_ORDER..ORDERS)
+            .rev()
+            .find(|&order| self.free[order].is_some())
+            .map(|order| 1 << order)
+            .unwrap_or(0)
+    }
+
+    fn usage(&self) -> Usage {
+        let free = self.free();
+        Usage {
+            free,
+            used: self.len - free,
+            largest_free_block: self.largest_free_block(),
+        }
+    }
+
+    /// Order of the block that serves `size` bytes aligned to `align`
+    fn order(size: usize, align: usize) -> Option<usize> {
+        let size = cmp::max(cmp::max(size, align), 1 << MIN_ORDER);
+        let order = (usize::BITS - (size - 1).leading_zeros()) as usize;
+
+        if order < ORDERS {
+            Some(order)
+        } else {
+            None
+        }
+    }
+
+    /// Alignment of `base`; blocks are guaranteed to be aligned to at most this value
+    fn max_align(&self) -> usize {
+        1 << self.base.trailing_zeros()
+    }
+
+    fn buddy_of(&self, addr: usize, order: usize) -> usize {
+        self.base + ((addr - self.base) ^ (1 << order))
+    }
+
+    fn iter(&self, order: usize) -> impl Iterator<Item = NonNull<Free>> + '_ {
+        let mut next = self.free[order];
+        core::iter::from_fn(move || {
+            let node = next?;
+            next = unsafe { node.as_ref().next };
+            Some(node)
+        })
+    }
+
+    unsafe fn push(&mut self, order: usize, addr: usize) {
+        let node = addr as *mut Free;
+        node.write(Free {
+            next: self.free[order],
+        });
+        self.free[order] = Some(NonNull::new_unchecked(node));
+    }
+
+    unsafe fn pop(&mut self, order: usize) -> Option<usize> {
+        let node = self.free[order]?;
+        self.free[order] = node.as_ref().next;
+        Some(node.as_ptr() as usize)
+    }
+
+    /// Removes the block at `addr` from the free list of the given `order`
+    ///
+    /// Returns `false` if the block is not in that free list
+    unsafe fn remove(&mut self, order: usize, addr: usize) -> bool {
+        let mut link = &mut self.free[order];
+        while let Some(mut node) = *link {
+            if node.as_ptr() as usize == addr {
+                *link = node.as_ref().next;
+                return true;
+            }
+
+            link = &mut node.as_mut().next;
+        }
+
+        false
+    }
+
+    fn is_free(&self, order: usize, addr: usize) -> bool {
+        self.iter(order).any(|node| node.as_ptr() as usize == addr)
+    }
+
+    fn layout_error(&self, layout: Layout) -> AllocError {
+        if layout.align() > self.max_align() {
+            AllocError::new(layout, AllocErrorKind::UnsupportedAlignment)
+        } else if Self::order(layout.size(), layout.align()).is_none() {
+            AllocError::new(layout, AllocErrorKind::SizeOverflow)
+        } else {
+            AllocError::exhausted(layout).with_usage(self.usage())
+        }
+    }
+}
+
+impl<const MIN_ORDER: usize> Alloc for Buddy<MIN_ORDER> {
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.alloc_excess(layout).map(|Excess(ptr, _)| ptr)
+    }
+
+    fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+        match Self::order(layout.size(), layout.align()) {
+            Some(order) => (layout.size(), 1 << order),
+            None => (layout.size(), layout.size()),
+        }
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        let order = match Self::order(layout.size(), layout.align()) {
+            Some(order) if layout.align() <= self.max_align() => order,
+            _ => return Err(self.layout_error(layout)),
+        };
+
+        let (mut current, addr) = match (order..ORDERS)
+            .find_map(|current| self.pop(current).map(|addr| (current, addr)))
+        {
+            Some(x) => x,
+            None => return Err(self.layout_error(layout)),
+        };
+
+        // split the block until it matches the request
+        while current > order {
+            current -= 1;
+            self.push(current, addr + (1 << current));
+        }
+
+        Ok(Excess(NonNull::new_unchecked(addr as *mut u8), 1 << order))
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let mut order = match Self::order(layout.size(), layout.align()) {
+            Some(order) => order,
+            None => return,
+        };
+        let mut addr = ptr.as_ptr() as usize;
+
+        // merge the block with its buddy for as long as the buddy is free
+        while order < ORDERS - 1 {
+            let buddy = self.buddy_of(addr, order);
+            if !self.remove(order, buddy) {
+                break;
+            }
+
+            addr = cmp::min(addr, buddy);
+            order += 1;
+        }
+
+        self.push(order, addr)
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let (order, new_order) = match (
+            Self::order(layout.size(), layout.align()),
+            Self::order(new_size, layout.align()),
+        ) {
+            (Some(order), Some(new_order)) => (order, new_order),
+            _ => return Err(AllocError::new(new_layout, AllocErrorKind::SizeOverflow)),
+        };
+        let addr = ptr.as_ptr() as usize;
+
+        // the block must be the lower half of every block up to `new_order` and all the upper
+        // halves (buddies) must be free
+        let mergeable = (addr - self.base) & ((1 << new_order) - 1) == 0
+            && (order..new_order).all(|order| self.is_free(order, addr + (1 << order)));
+
+        if !mergeable {
+            return Err(AllocError::exhausted(new_layout).with_usage(self.usage()));
+        }
+
+        for order in order..new_order {
+            self.remove(order, addr + (1 << order));
+        }
+
+        Ok(())
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        let (order, new_order) = match (
+            Self::order(layout.size(), layout.align()),
+            Self::order(new_size, layout.align()),
+        ) {
+            (Some(order), Some(new_order)) => (order, new_order),
+            _ => {
+                return Err(AllocError::new(
+                    Layout::from_size_align_unchecked(new_size, layout.align()),
+                    AllocErrorKind::SizeOverflow,
+                ))
+            }
+        };
+        let addr = ptr.as_ptr() as usize;
+
+        // split off the upper halves; their buddies are part of this block so they can't be merged
+        for order in (new_order..order).rev() {
+            self.push(order, addr + (1 << order));
+        }
+
+        Ok(())
+    }
+}
+
+impl<const MIN_ORDER: usize> Owns for Buddy<MIN_ORDER> {
+    fn owns(&self, ptr: NonNull<u8>, _layout: Layout) -> bool {
+        let addr = ptr.as_ptr() as usize;
+        addr >= self.base && addr < self.base + self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::alloc::Layout;
+    use std::{boxed::Box, vec::Vec};
+
+    use alloc_trait::{Alloc, Excess};
+
+    use super::Buddy;
+
+    #[repr(align(256))]
+    struct Memory([u8; 256]);
+
+    fn buddy() -> Buddy {
+        Buddy::new(&mut Box::leak(Box::new(Memory([0; 256]))).0)
+    }
+
+    fn free_orders(buddy: &Buddy) -> Vec<usize> {
+        (0..super::ORDERS)
+            .flat_map(|order| buddy.iter(order).map(move |_| order))
+            .collect()
+    }
+
+    #[test]
+    fn split_merge() {
+        let mut buddy = buddy();
+        assert_eq!(free_orders(&buddy), [8]);
+
+        let layout = Layout::new::<u8>();
+        unsafe {
+            let Excess(ptr, size) = buddy.alloc_excess(layout).unwrap();
+            assert_eq!(size, 16);
+            assert_eq!(ptr.as_ptr() as usize, buddy.base);
+            // the 256-byte block was split down to a 16-byte block
+            assert_eq!(free_orders(&buddy), [4, 5, 6, 7]);
+            assert_eq!(buddy.free(), 240);
+            assert_eq!(buddy.largest_free_block(), 128);
+
+            // the buddy of the first block
+            let buddy_ptr = buddy.alloc(layout).unwrap();
+            assert_eq!(buddy_ptr.as_ptr() as usize, buddy.base + 16);
+
+            buddy.dealloc(ptr, layout);
+            // the buddy is in use: no merge
+            assert_eq!(free_orders(&buddy), [4, 5, 6, 7]);
+
+            buddy.dealloc(buddy_ptr, layout);
+            assert_eq!(free_orders(&buddy), [8]);
+            assert_eq!(buddy.free(), 256);
+        }
+    }
+
+    #[test]
+    fn grow_in_place() {
+        let mut buddy = buddy();
+        let layout = Layout::from_size_align(16, 16).unwrap();
+
+        unsafe {
+            let ptr = buddy.alloc(layout).unwrap();
+            buddy.grow_in_place(ptr, layout, 64).unwrap();
+            assert_eq!(free_orders(&buddy), [6, 7]);
+
+            let layout = Layout::from_size_align(64, 16).unwrap();
+            buddy.grow_in_place(ptr, layout, 256).unwrap();
+            assert_eq!(buddy.free(), 0);
+
+            buddy.dealloc(ptr, Layout::from_size_align(256, 16).unwrap());
+            assert_eq!(free_orders(&buddy), [8]);
+        }
+    }
+
+    #[test]
+    fn grow_in_place_fails() {
+        let mut buddy = buddy();
+        let layout = Layout::from_size_align(16, 16).unwrap();
+
+        unsafe {
+            let a = buddy.alloc(layout).unwrap();
+            let b = buddy.alloc(layout).unwrap();
+
+            // `b`, the buddy of `a`, is in use
+            assert!(buddy.grow_in_place(a, layout, 32).is_err());
+            // `b` is the upper half of the 32-byte block
+            assert!(buddy.grow_in_place(b, layout, 32).is_err());
+            assert_eq!(free_orders(&buddy), [5, 6, 7]);
+
+            buddy.dealloc(b, layout);
+            buddy.grow_in_place(a, layout, 32).unwrap();
+            assert_eq!(free_orders(&buddy), [5, 6, 7]);
+        }
+    }
+
+    #[test]
+    fn shrink_in_place() {
+        let mut buddy = buddy();
+        let layout = Layout::from_size_align(256, 16).unwrap();
+
+        unsafe {
+            let ptr = buddy.alloc(layout).unwrap();
+            buddy.shrink_in_place(ptr, layout, 16).unwrap();
+            assert_eq!(free_orders(&buddy), [4, 5, 6, 7]);
+
+            buddy.dealloc(ptr, Layout::from_size_align(16, 16).unwrap());
+            assert_eq!(free_orders(&buddy), [8]);
+        }
+    }
+
+    #[test]
+    fn power_of_two_buffers() {
+        let mut buddy = buddy();
+        let layout = Layout::from_size_align(64, 64).unwrap();
+
+        // aligned power-of-two buffers use the whole heap
+        unsafe {
+            for _ in 0..4 {
+                let ptr = buddy.alloc(layout).unwrap();
+                assert_eq!(ptr.as_ptr() as usize % 64, 0);
+            }
+            assert!(buddy.alloc(layout).is_err());
+        }
+    }
+}
@@ -1,12 +1,16 @@
 use core::{alloc::Layout, cmp, fmt, ops, pin::Pin, ptr};
 
-use alloc_trait::Alloc;
+#[cfg(feature = "generator")]
+use alloc_trait::AllocError;
+use alloc_trait::Allocator;
 
 use crate::unique::Unique;
+#[cfg(feature = "generator")]
+use crate::wait::Wait;
 
 pub struct Box<T, A>
 where
-    A: Alloc,
+    A: Allocator,
     T: ?Sized,
 {
     allocator: A,
@@ -15,28 +19,63 @@ where
 
 impl<A, T> Box<T, A>
 where
-    A: Alloc,
+    A: Allocator,
 {
     /// Allocates memory on the allocator `A` and then places `x` into it.
     pub fn new(value: T, mut allocator: A) -> Self {
         let ptr = Unique::alloc(value, &mut allocator);
         Box { allocator, ptr }
     }
+
+    /// Like `new` but waits for memory to become available instead of calling the Out-Of-Memory
+    /// handler; on failure `value` is handed back
+    #[cfg(feature = "generator")]
+    pub fn new_async(
+        value: T,
+        allocator: A,
+        wait: Wait,
+    ) -> impl ops::Coroutine<Yield = (), Return = Result<Self, (T, AllocError)>> {
+        crate::wait::attempts(
+            (value, allocator),
+            wait,
+            |(_, allocator)| allocator.allocate(Layout::new::<T>()),
+            |(value, allocator), res| match res {
+                Ok(block) => unsafe {
+                    let ptr = block.cast::<T>().as_ptr();
+                    ptr.write(value);
+                    Ok(Box {
+                        allocator,
+                        ptr: Unique::new_unchecked(ptr),
+                    })
+                },
+                Err(e) => Err((value, e)),
+            },
+        )
+    }
 }
 
 #[cfg(feature = "coerce")]
 impl<A, T, U> ops::CoerceUnsized<Box<U, A>> for Box<T, A>
 where
-    A: Alloc,
+    A: Allocator,
     T: ?Sized + core::marker::Unsize<U>,
     U: ?Sized,
 {
 }
 
+// NOTE the pointee doesn't move when the box is moved
+#[cfg(feature = "coerce")]
+unsafe impl<A, T> core::pin::PinCoerceUnsized for Box<T, A>
+where
+    A: Allocator,
+    T: ?Sized,
+{
+}
+
 impl<A, T> ops::Deref for Box<T, A>
 where
     T: ?Sized,
-    A: Alloc,
+    A: Allocator,
 {
     type Target = T;
 
@@ -48,7 +87,7 @@ where
 impl<A, T> ops::DerefMut for Box<T, A>
 where
     T: ?Sized,
-    A: Alloc,
+    A: Allocator,
 {
     fn deref_mut(&mut self) -> &mut T {
         unsafe { &mut *self.ptr.as_ptr() }
@@ -57,22 +96,25 @@ where
 
 impl<A, T> Drop for Box<T, A>
 where
-    A: Alloc,
+    A: Allocator,
     T: ?Sized,
 {
     fn drop(&mut self) {
         unsafe {
             let layout = Layout::for_value(self.ptr.as_ref());
             ptr::drop_in_place(self.ptr.as_ptr());
-            self.allocator.dealloc((*self.ptr).cast(), layout)
+            self.allocator.deallocate((*self.ptr).cast(), layout);
         }
+
+        #[cfg(feature = "generator")]
+        crate::wait::notify_free();
     }
 }
 
 impl<A, T> fmt::Debug for Box<T, A>
 where
     T: ?Sized + fmt::Debug,
-    A: Alloc,
+    A: Allocator,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         <T as fmt::Debug>::fmt(self, f)
@@ -82,7 +124,7 @@ where
 impl<A, T> fmt::Display for Box<T, A>
 where
     T: ?Sized + fmt::Display,
-    A: Alloc,
+    A: Allocator,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         <T as fmt::Display>::fmt(self, f)
@@ -92,50 +134,50 @@ where
 impl<A, T> Eq for Box<T, A>
 where
     T: ?Sized + Eq,
-    A: Alloc,
+    A: Allocator,
 {
 }
 
 impl<A, T> Unpin for Box<T, A>
 where
-    A: Alloc,
+    A: Allocator,
     T: ?Sized,
 {
 }
 
 #[cfg(feature = "generator")]
-impl<A, G> ops::Generator for Box<G, A>
+impl<A, G, R> ops::Coroutine<R> for Box<G, A>
 where
-    A: Alloc,
-    G: ops::Generator + Unpin + ?Sized,
+    A: Allocator,
+    G: ops::Coroutine<R> + Unpin + ?Sized,
 {
     type Yield = G::Yield;
     type Return = G::Return;
 
-    fn resume(mut self: Pin<&mut Self>) -> ops::GeneratorState<G::Yield, G::Return> {
-        G::resume(Pin::new(&mut *self))
+    fn resume(mut self: Pin<&mut Self>, arg: R) -> ops::CoroutineState<G::Yield, G::Return> {
+        G::resume(Pin::new(&mut *self), arg)
     }
 }
 
 #[cfg(feature = "generator")]
-impl<A, G> ops::Generator for Pin<Box<G, A>>
+impl<A, G, R> ops::Coroutine<R> for Pin<Box<G, A>>
 where
-    A: Alloc,
-    G: ops::Generator + ?Sized,
+    A: Allocator,
+    G: ops::Coroutine<R> + ?Sized,
 {
     type Yield = G::Yield;
     type Return = G::Return;
 
-    fn resume(mut self: Pin<&mut Self>) -> ops::GeneratorState<G::Yield, G::Return> {
-        G::resume((*self).as_mut())
+    fn resume(mut self: Pin<&mut Self>, arg: R) -> ops::CoroutineState<G::Yield, G::Return> {
+        G::resume((*self).as_mut(), arg)
     }
 }
 
 impl<A, B, T> PartialEq<Box<T, B>> for Box<T, A>
 where
     T: ?Sized + PartialEq,
-    A: Alloc,
-    B: Alloc,
+    A: Allocator,
+    B: Allocator,
 {
     fn eq(&self, other: &Box<T, B>) -> bool {
         <T as PartialEq>::eq(self, other)
@@ -145,8 +187,8 @@ where
 impl<A, B, T> PartialOrd<Box<T, B>> for Box<T, A>
 where
     T: ?Sized + PartialOrd,
-    A: Alloc,
-    B: Alloc,
+    A: Allocator,
+    B: Allocator,
 {
     fn partial_cmp(&self, other: &Box<T, B>) -> Option<cmp::Ordering> {
         <T as PartialOrd>::partial_cmp(self, other)
@@ -155,10 +197,66 @@ where
 
 impl<A, T> From<Box<T, A>> for Pin<Box<T, A>>
 where
-    A: Alloc,
+    A: Allocator,
     T: ?Sized,
 {
     fn from(boxed: Box<T, A>) -> Self {
         unsafe { Pin::new_unchecked(boxed) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::cell::Cell;
+    use std::alloc::System;
+
+    use alloc_trait::Global;
+
+    use super::Box;
+
+    struct Droppable<'a>(&'a Cell<usize>);
+
+    impl Drop for Droppable<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn new() {
+        let mut b = Box::new([1u32, 2, 3], Global(System));
+        assert_eq!(*b, [1, 2, 3]);
+
+        b[1] = 4;
+        assert_eq!(*b, [1, 4, 3]);
+        assert_eq!(b, Box::new([1, 4, 3], Global(System)));
+    }
+
+    #[test]
+    fn drop() {
+        let drops = Cell::new(0);
+
+        let b = Box::new(Droppable(&drops), Global(System));
+        assert_eq!(drops.get(), 0);
+        core::mem::drop(b);
+        assert_eq!(drops.get(), 1);
+
+        // zero-sized values are dropped too
+        let b = Box::new((), Global(System));
+        core::mem::drop(b);
+    }
+
+    #[test]
+    fn checked() {
+        let drops = Cell::new(0);
+
+        let b = Box::new(Droppable(&drops), crate::tests::checked());
+        core::mem::drop(b);
+        assert_eq!(drops.get(), 1);
+
+        let b = Box::new([0u8; 3], crate::tests::checked());
+        assert_eq!(*b, [0; 3]);
+    }
+}
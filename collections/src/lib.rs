@@ -6,8 +6,10 @@
 
 #![allow(dead_code)]
 #![cfg_attr(feature = "coerce", feature(coerce_unsized))]
+#![cfg_attr(feature = "coerce", feature(pin_coerce_unsized_trait))]
 #![cfg_attr(feature = "coerce", feature(unsize))]
-#![cfg_attr(feature = "generator", feature(generator_trait))]
+#![cfg_attr(feature = "generator", feature(coroutine_trait))]
+#![cfg_attr(feature = "generator", feature(coroutines))]
 #![cfg_attr(feature = "rc", feature(core_intrinsics))]
 #![cfg_attr(feature = "rc", feature(dropck_eyepatch))]
 #![deny(rust_2018_compatibility)]
@@ -22,3 +24,30 @@ pub mod boxed;
 pub mod rc;
 mod unique;
 pub mod vec;
+#[cfg(feature = "generator")]
+pub mod wait;
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::alloc::Layout;
+    use std::alloc::System;
+
+    use alloc_trait::{Checked, Global, Violation};
+
+    // NOTE the tests don't exercise the Out-Of-Memory path
+    #[alloc_oom::oom]
+    fn oom(layout: Layout) -> ! {
+        panic!("out of memory: {:?}", layout)
+    }
+
+    fn report(violation: Violation) {
+        panic!("allocator misuse: {:?}", violation)
+    }
+
+    /// The system allocator, checked for misuse by the collections
+    pub(crate) fn checked() -> Checked<Global<System>> {
+        Checked::new(Global(System), report)
+    }
+}
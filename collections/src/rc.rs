@@ -7,7 +7,7 @@ use core::{
     ptr::{self, NonNull},
 };
 
-use alloc_trait::Alloc;
+use alloc_trait::Allocator;
 
 use crate::unique::Unique;
 
@@ -22,7 +22,7 @@ where
 
 pub struct Rc<T, A>
 where
-    A: Alloc,
+    A: Allocator,
     T: ?Sized,
 {
     // NOTE alternatively the `allocator` could be stored in `RcBox`
@@ -33,7 +33,7 @@ where
 
 impl<T, A> Rc<T, A>
 where
-    A: Alloc,
+    A: Allocator,
     T: ?Sized,
 {
     pub fn new(value: T, mut allocator: A) -> Rc<T, A>
@@ -99,7 +99,7 @@ where
 impl<A, T> Clone for Rc<T, A>
 where
     T: ?Sized,
-    A: Alloc + Copy,
+    A: Allocator + Copy,
 {
     fn clone(&self) -> Self {
         self.inc_strong();
@@ -110,7 +110,7 @@ where
 unsafe impl<A, #[may_dangle] T> Drop for Rc<T, A>
 where
     T: ?Sized,
-    A: Alloc,
+    A: Allocator,
 {
     fn drop(&mut self) {
         unsafe {
@@ -122,8 +122,11 @@ where
 
                 // if self.weak() == 0 {
                 self.allocator
-                    .dealloc(self.ptr.cast(), Layout::for_value(self.ptr.as_ref()));
+                    .deallocate(self.ptr.cast(), Layout::for_value(self.ptr.as_ref()));
                 // }
+
+                #[cfg(feature = "generator")]
+                crate::wait::notify_free();
             }
         }
     }
@@ -132,7 +135,7 @@ where
 impl<T, A> ops::Deref for Rc<T, A>
 where
     T: ?Sized,
-    A: Alloc,
+    A: Allocator,
 {
     type Target = T;
 
@@ -1,6 +1,6 @@
-use core::{alloc::Layout, marker::PhantomData, mem, ops, ptr::NonNull};
+use core::{alloc::Layout, marker::PhantomData, ops, ptr::NonNull};
 
-use alloc_trait::Alloc;
+use alloc_trait::Allocator;
 
 pub struct Unique<T>
 where
@@ -18,7 +18,7 @@ where
     where
         T: Sized,
     {
-        unsafe { Self::new_unchecked(mem::align_of::<T>() as *mut T) }
+        unsafe { Self::new_unchecked(NonNull::dangling().as_ptr()) }
     }
 
     pub const unsafe fn new_unchecked(ptr: *mut T) -> Self {
@@ -37,26 +37,15 @@ where
 
     pub(crate) fn alloc<A>(value: T, allocator: &mut A) -> Self
     where
-        A: Alloc,
+        A: Allocator,
         T: Sized,
     {
         unsafe {
-            if mem::size_of::<T>() == 0 {
-                Unique::new_unchecked(mem::align_of::<T>() as *mut _)
-            } else {
-                let layout = Layout::new::<T>();
+            // NOTE `allocate` returns a dangling, well-aligned pointer for zero-sized types
+            let nn = alloc_oom::retry(|| allocator.allocate(Layout::new::<T>())).cast::<T>();
+            nn.as_ptr().write(value);
 
-                allocator
-                    .alloc(layout)
-                    .ok()
-                    .map(|nn| {
-                        let nn = nn.cast::<T>();
-                        nn.as_ptr().write(value);
-
-                        Unique::new_unchecked(nn.as_ptr())
-                    })
-                    .unwrap_or_else(|| alloc_oom::oom(layout))
-            }
+            Unique::new_unchecked(nn.as_ptr())
         }
     }
 }
@@ -1,12 +1,17 @@
+#[cfg(feature = "generator")]
+use core::ops::Coroutine;
 use core::{alloc::Layout, cmp, mem, ops, ptr, slice};
 
-use alloc_trait::Alloc;
+use alloc_oom::{OomInfo, Operation};
+use alloc_trait::{AllocError, Allocator};
 
 use crate::unique::Unique;
+#[cfg(feature = "generator")]
+use crate::wait::Wait;
 
 pub struct Vec<T, A>
 where
-    A: Alloc,
+    A: Allocator,
 {
     allocator: A,
     cap: usize,
@@ -16,11 +21,11 @@ where
 
 impl<A, T> Vec<T, A>
 where
-    A: Alloc,
+    A: Allocator,
 {
     pub fn new(allocator: A) -> Self {
         let cap = if mem::size_of::<T>() == 0 {
-            usize::max_value()
+            usize::MAX
         } else {
             0
         };
@@ -53,25 +58,39 @@ where
             return;
         }
 
-        unsafe {
-            let (new_cap, new_layout) = amortized_new_capacity(self.len, additional)
-                .and_then(|new_cap| layout_array::<T>(new_cap).map(|layout| (new_cap, layout)))
-                .unwrap_or_else(|| capacity_overflow());
-
-            let res = match self.current_layout() {
-                None => self.allocator.alloc(new_layout),
-                Some(layout) => self
-                    .allocator
-                    .realloc(self.ptr.cast(), layout, new_layout.size()),
-            };
+        alloc_oom::retry(|| self.grow(additional))
+    }
 
-            self.ptr = Unique::new_unchecked(
-                res.unwrap_or_else(|_| alloc_oom::oom(new_layout))
-                    .as_ptr()
-                    .cast(),
-            );
-            self.cap = new_cap;
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        if self.cap.wrapping_sub(self.len) >= additional {
+            return Ok(());
         }
+
+        alloc_oom::try_retry(|| self.grow(additional))
+    }
+
+    /// Like `reserve` but waits for memory to become available instead of calling the
+    /// Out-Of-Memory handler
+    ///
+    /// The vector is moved into the generator, which hands it back on completion
+    #[cfg(feature = "generator")]
+    pub fn reserve_async(
+        self,
+        additional: usize,
+        wait: Wait,
+    ) -> impl Coroutine<Yield = (), Return = (Self, Result<(), AllocError>)> {
+        crate::wait::attempts(
+            self,
+            wait,
+            move |this| {
+                if this.cap.wrapping_sub(this.len) >= additional {
+                    Ok(())
+                } else {
+                    this.grow(additional).map_err(|info| info.error())
+                }
+            },
+            |this, res| (this, res),
+        )
     }
 
     pub fn swap_remove(&mut self, index: usize) -> T {
@@ -86,6 +105,31 @@ where
         }
     }
 
+    /// Grows the buffer so it can hold at least `additional` more elements
+    fn grow(&mut self, additional: usize) -> Result<(), OomInfo> {
+        unsafe {
+            let (new_cap, new_layout) = amortized_new_capacity(self.len, additional)
+                .and_then(|new_cap| layout_array::<T>(new_cap).map(|layout| (new_cap, layout)))
+                .unwrap_or_else(|| capacity_overflow());
+
+            let block = match self.current_layout() {
+                None => self
+                    .allocator
+                    .allocate(new_layout)
+                    .map_err(|e| OomInfo::new(e, Operation::Alloc)),
+                Some(layout) => self
+                    .allocator
+                    .grow(self.ptr.cast(), layout, new_layout)
+                    .map_err(|e| OomInfo::new(e, Operation::Realloc)),
+            }?;
+            self.ptr = Unique::new_unchecked(block.as_ptr().cast());
+            // the allocator may have handed us a larger block than requested; use all of it
+            self.cap = cmp::max(new_cap, block.len() / mem::size_of::<T>());
+        }
+
+        Ok(())
+    }
+
     fn current_layout(&self) -> Option<Layout> {
         if self.cap == 0 {
             None
@@ -112,7 +156,7 @@ fn capacity_overflow() -> ! {
 
 impl<A, T> ops::Deref for Vec<T, A>
 where
-    A: Alloc,
+    A: Allocator,
 {
     type Target = [T];
 
@@ -123,7 +167,7 @@ where
 
 impl<A, T> ops::DerefMut for Vec<T, A>
 where
-    A: Alloc,
+    A: Allocator,
 {
     fn deref_mut(&mut self) -> &mut [T] {
         unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
@@ -159,3 +203,147 @@ fn padding_needed_for(layout: &Layout, align: usize) -> usize {
 
     len_rounded_up.wrapping_sub(len)
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{alloc::Layout, ptr::NonNull};
+    use std::alloc::System;
+
+    use alloc_trait::{Alloc, AllocError, Global};
+
+    use super::Vec;
+
+    /// Hands out blocks rounded up to a multiple of 64 bytes
+    struct Rounding;
+
+    fn round(layout: Layout) -> Layout {
+        Layout::from_size_align((layout.size() + 63) & !63, layout.align()).unwrap()
+    }
+
+    impl Alloc for Rounding {
+        unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            Global(System).alloc(round(layout))
+        }
+
+        unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+            Global(System).dealloc(ptr, round(layout))
+        }
+
+        fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+            (layout.size(), round(*layout).size())
+        }
+
+        unsafe fn grow_in_place(
+            &mut self,
+            _ptr: NonNull<u8>,
+            layout: Layout,
+            new_size: usize,
+        ) -> Result<(), AllocError> {
+            if new_size <= round(layout).size() {
+                Ok(())
+            } else {
+                Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+                    new_size,
+                    layout.align(),
+                )))
+            }
+        }
+
+        unsafe fn shrink_in_place(
+            &mut self,
+            _ptr: NonNull<u8>,
+            layout: Layout,
+            new_size: usize,
+        ) -> Result<(), AllocError> {
+            Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+                new_size,
+                layout.align(),
+            )))
+        }
+    }
+
+    #[test]
+    fn push() {
+        let mut v = Vec::new(Global(System));
+        for i in 0..100 {
+            v.push(i);
+        }
+
+        assert_eq!(v.len(), 100);
+        assert!(v.capacity() >= 100);
+        assert!(v.iter().copied().eq(0..100));
+
+        assert_eq!(v.swap_remove(0), 0);
+        assert_eq!(v.len(), 99);
+        assert_eq!(v[0], 99);
+
+        v[1] = -1;
+        assert_eq!(v[1], -1);
+    }
+
+    #[test]
+    fn reserve() {
+        let mut v = Vec::new(Global(System));
+        v.push(0u64);
+
+        v.reserve(10);
+        let cap = v.capacity();
+        assert!(cap >= 11);
+
+        // no reallocation while there's spare capacity
+        let ptr = v.as_ptr();
+        for i in 1..cap as u64 {
+            v.push(i);
+        }
+        assert_eq!(v.capacity(), cap);
+        assert_eq!(v.as_ptr(), ptr);
+        assert!(v.iter().copied().eq(0..cap as u64));
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut v = Vec::new(Global(System));
+        assert_eq!(v.capacity(), usize::MAX);
+
+        for _ in 0..3 {
+            v.push(());
+        }
+        assert_eq!(v.len(), 3);
+        assert_eq!(v.capacity(), usize::MAX);
+    }
+
+    #[test]
+    fn excess() {
+        // the whole block is used, not just the requested part of it
+        let mut v: Vec<u8, _> = Vec::new(Rounding);
+        v.reserve(3);
+        assert_eq!(v.capacity(), 64);
+
+        for i in 0..64 {
+            v.push(i);
+        }
+        assert_eq!(v.capacity(), 64);
+
+        // this grows the block from 64 to 164 bytes, rounded up to 192 bytes
+        v.reserve(100);
+        assert_eq!(v.capacity(), 192);
+        assert!(v.iter().copied().eq(0..64));
+    }
+
+    #[test]
+    fn checked() {
+        let mut v = Vec::new(crate::tests::checked());
+        for i in 0..100u32 {
+            v.push(i);
+        }
+        v.reserve(1_000);
+        assert_eq!(v.swap_remove(0), 0);
+        assert_eq!(v.len(), 99);
+
+        let mut v: Vec<u64, _> = Vec::new(crate::tests::checked());
+        v.reserve(3);
+        v.push(0);
+    }
+}
@@ -0,0 +1,164 @@
+//! Asynchronous allocation: waiting for memory instead of running out of it
+//!
+//! The generators returned by [`allocate`], `Box::new_async` and `Vec::reserve_async` retry the
+//! allocation request every time they are resumed, yielding in between, until it succeeds or the
+//! [`Wait`] policy gives up. Drive them with `gen_async_await::r#await!`.
+
+use core::{
+    alloc::Layout,
+    ops::Coroutine,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc_trait::{AllocError, Allocator};
+
+/// Number of times memory has been freed (wrapping)
+static FREES: AtomicUsize = AtomicUsize::new(0);
+
+/// Signals that memory has been freed; this wakes the allocations that wait using
+/// [`Wait::on_free`]
+///
+/// `Box` and `Rc` call this function when they release their memory; call it after freeing memory
+/// by other means
+pub fn notify_free() {
+    // NOTE load + store rather than `fetch_add` because not all targets have CAS; a lost update
+    // still changes the counter, which is all the waiters look at
+    FREES.store(
+        FREES.load(Ordering::Relaxed).wrapping_add(1),
+        Ordering::Relaxed,
+    );
+}
+
+/// How an asynchronous allocation waits for memory
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Wait {
+    attempts: Option<usize>,
+    on_free: bool,
+}
+
+impl Wait {
+    /// Retries on every resumption, without limit
+    pub const fn new() -> Self {
+        Self {
+            attempts: None,
+            on_free: false,
+        }
+    }
+
+    /// Gives up after `n` failed attempts; `n` is at least 1
+    pub const fn attempts(self, n: usize) -> Self {
+        Self {
+            attempts: Some(n),
+            ..self
+        }
+    }
+
+    /// Only retries after memory has been freed (see [`notify_free`]); resumptions that happen
+    /// before that yield right away and don't count as attempts
+    pub const fn on_free(self) -> Self {
+        Self {
+            on_free: true,
+            ..self
+        }
+    }
+}
+
+impl Default for Wait {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The state of an asynchronous allocation
+struct Waiting {
+    wait: Wait,
+    failures: usize,
+    /// Value of `FREES` when the last attempt was made
+    frees: Option<usize>,
+}
+
+impl Waiting {
+    fn new(wait: Wait) -> Self {
+        Self {
+            wait,
+            failures: 0,
+            frees: None,
+        }
+    }
+
+    /// Calls `f` unless the allocation is waiting for memory to be freed
+    ///
+    /// Returns `None` if the caller must yield
+    fn attempt<R>(
+        &mut self,
+        f: impl FnOnce() -> Result<R, AllocError>,
+    ) -> Option<Result<R, AllocError>> {
+        let frees = FREES.load(Ordering::Relaxed);
+        if self.wait.on_free && self.frees == Some(frees) {
+            return None;
+        }
+
+        match f() {
+            Ok(x) => Some(Ok(x)),
+            Err(e) => {
+                self.failures += 1;
+                self.frees = Some(frees);
+
+                if self
+                    .wait
+                    .attempts
+                    .map(|n| self.failures >= n)
+                    .unwrap_or(false)
+                {
+                    Some(Err(e))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Allocates a block of memory, waiting for memory to become available
+///
+/// The returned generator completes with the allocated block, or with the error of the last
+/// attempt if the `wait` policy gave up
+pub fn allocate<A>(
+    allocator: A,
+    layout: Layout,
+    wait: Wait,
+) -> impl Coroutine<Yield = (), Return = Result<NonNull<[u8]>, AllocError>>
+where
+    A: Allocator,
+{
+    attempts(
+        allocator,
+        wait,
+        move |allocator| allocator.allocate(layout),
+        |_, res| res,
+    )
+}
+
+/// Calls `f` on `state` until it succeeds or the `wait` policy gives up, yielding in between;
+/// then completes with `finish(state, result)`
+// NOTE all the `yield`s of this crate live in this module: `yield` is feature gated even in code
+// that has been `cfg`-ed away
+pub(crate) fn attempts<S, R, T>(
+    mut state: S,
+    wait: Wait,
+    mut f: impl FnMut(&mut S) -> Result<R, AllocError>,
+    finish: impl FnOnce(S, Result<R, AllocError>) -> T,
+) -> impl Coroutine<Yield = (), Return = T> {
+    #[coroutine]
+    move || {
+        let mut waiting = Waiting::new(wait);
+        loop {
+            if let Some(res) = waiting.attempt(|| f(&mut state)) {
+                break finish(state, res);
+            }
+
+            yield
+        }
+    }
+}
@@ -3,6 +3,8 @@ use std::{env, error::Error};
 fn main() -> Result<(), Box<dyn Error>> {
     let target = env::var("TARGET")?;
 
+    println!("cargo:rustc-check-cfg=cfg(cortex_m)");
+
     match &target[..] {
         "thumbv6m-none-eabi"
         | "thumbv7m-none-eabi"
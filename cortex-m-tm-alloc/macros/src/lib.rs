@@ -5,28 +5,25 @@ use std::collections::HashSet;
 
 use proc_macro2::Span;
 use quote::quote;
-use syn::{parse, parse_macro_input, Expr, Ident, Item, ItemStatic, Stmt, Visibility};
+use syn::{
+    parse::{self, Parse, ParseStream},
+    parse_macro_input, Expr, Ident, Item, ItemStatic, Path, Stmt, Token, Visibility,
+};
 
 /// Declares an allocator that can only be used in "thread-mode" (AKA `#[entry]`, `#[init]` or
 /// `#[idle]`)
+///
+/// Arguments:
+///
+/// - `lazy`: initialize the allocator at runtime, the first time it's `get`-ed
+/// - `oom = path::to::handler`: Out-Of-Memory handler dedicated to this allocator; it takes an
+///   `alloc_oom::OomInfo` and returns either `!` or an `alloc_oom::Decision`. Allocators without a
+///   dedicated handler use the global `#[oom]` handler. Like a diverging global handler, a
+///   diverging dedicated handler is recorded (see `alloc_oom::record`) before it runs.
 #[proc_macro_attribute]
 pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
-    let lazy = if args.is_empty() {
-        false
-    } else {
-        let args = args.to_string();
-
-        if args == "lazy" {
-            true
-        } else {
-            return parse::Error::new(
-                Span::call_site(),
-                &format!("expected `lazy`, found `{}`", args),
-            )
-            .to_compile_error()
-            .into();
-        }
-    };
+    let args = parse_macro_input!(args as Args);
+    let lazy = args.lazy;
 
     let item = parse_macro_input!(item as ItemStatic);
 
@@ -49,6 +46,19 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
     }
 
     let krate = Ident::new("cortex_m_tm_alloc", Span::call_site());
+    let oom = if let Some(handler) = &args.oom {
+        quote!(
+            // a diverging handler ends the program's normal flow so the condition is recorded
+            // beforehand
+            fn oom(info: #krate::OomInfo) -> #krate::Decision {
+                #krate::DedicatedHandler::call(#handler as fn(_) -> _, info)
+            }
+
+            error.with_handler(oom)
+        )
+    } else {
+        quote!(error)
+    };
     let ident = &item.ident;
     let ty = &item.ty;
     let expr = item.expr;
@@ -62,15 +72,18 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
             let statics = statics
                 .into_iter()
                 .map(|statik| {
+                    let attrs = &statik.attrs;
                     let ident = &statik.ident;
                     let expr = &statik.expr;
                     let ty = &statik.ty;
 
+                    // NOTE attributes like `#[link_section]` place the memory in a specific RAM bank
                     quote!(
                         #[allow(non_snake_case)]
                         let #ident: &'static mut #ty = {
+                            #(#attrs)*
                             static mut #ident: #ty = #expr;
-                            unsafe { &mut #ident }
+                            unsafe { &mut *core::ptr::addr_of_mut!(#ident) }
                         };
                     )
                 })
@@ -113,7 +126,7 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
             fn _ptr() -> *mut #ty {
                 static mut #ident: core::mem::MaybeUninit<#ty> = core::mem::MaybeUninit::uninit();
 
-                unsafe { #ident.as_mut_ptr() }
+                unsafe { core::ptr::addr_of_mut!(#ident).cast() }
             }
         )
     } else {
@@ -132,7 +145,7 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
             fn _ptr() -> *mut #ty {
                 static mut #ident: #ty = #expr;
 
-                unsafe { &mut #ident }
+                unsafe { core::ptr::addr_of_mut!(#ident) }
             }
         )
     };
@@ -144,8 +157,29 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
 
         impl Copy for #ident {}
 
+        // all the copies of the handle refer to the same static variable
+        unsafe impl #krate::Handle for #ident {}
+
         impl #ident {
             #fns
+
+            /// IMPLEMENTATION DETAIL -- DO NOT USE
+            #[doc(hidden)]
+            pub fn _error(error: #krate::AllocError) -> #krate::AllocError {
+                let error = error.with_allocator(stringify!(#ident));
+                #oom
+            }
+
+            /// Grants `f` access to the allocator
+            ///
+            /// # Safety
+            ///
+            /// `f` must not use this allocator, e.g. by allocating through a copy of this handle
+            /// or by calling `with` again: that would create a second `&mut` reference to the
+            /// allocator
+            pub unsafe fn with<R>(&self, f: impl FnOnce(&mut #ty) -> R) -> R {
+                f(&mut *Self::_ptr())
+            }
         }
 
         impl core::fmt::Debug for #ident {
@@ -158,8 +192,9 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
             unsafe fn alloc(
                 &mut self,
                 layout: core::alloc::Layout,
-            ) -> Result<core::ptr::NonNull<u8>, ()> {
+            ) -> Result<core::ptr::NonNull<u8>, #krate::AllocError> {
                 <#ty as #krate::Alloc>::alloc(&mut *Self::_ptr(), layout)
+                    .map_err(Self::_error)
             }
 
             unsafe fn dealloc(
@@ -170,18 +205,31 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
                 <#ty as #krate::Alloc>::dealloc(&mut *Self::_ptr(), ptr, layout)
             }
 
+            fn usable_size(&self, layout: &core::alloc::Layout) -> (usize, usize) {
+                <#ty as #krate::Alloc>::usable_size(unsafe { &*Self::_ptr() }, layout)
+            }
+
+            unsafe fn alloc_excess(
+                &mut self,
+                layout: core::alloc::Layout,
+            ) -> Result<#krate::Excess, #krate::AllocError> {
+                <#ty as #krate::Alloc>::alloc_excess(&mut *Self::_ptr(), layout)
+                    .map_err(Self::_error)
+            }
+
             unsafe fn grow_in_place(
                 &mut self,
                 ptr: core::ptr::NonNull<u8>,
                 layout: core::alloc::Layout,
                 new_size: usize,
-            ) -> Result<(), ()> {
+            ) -> Result<(), #krate::AllocError> {
                 <#ty as #krate::Alloc>::grow_in_place(
                     &mut *Self::_ptr(),
                     ptr,
                     layout,
                     new_size,
                 )
+                .map_err(Self::_error)
             }
 
             unsafe fn shrink_in_place(
@@ -189,13 +237,14 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
                 ptr: core::ptr::NonNull<u8>,
                 layout: core::alloc::Layout,
                 new_size: usize,
-            ) -> Result<(), ()> {
+            ) -> Result<(), #krate::AllocError> {
                 <#ty as #krate::Alloc>::shrink_in_place(
                     &mut *Self::_ptr(),
                     ptr,
                     layout,
                     new_size,
                 )
+                .map_err(Self::_error)
             }
 
             unsafe fn realloc(
@@ -203,26 +252,76 @@ pub fn allocator(args: TokenStream, item: TokenStream) -> TokenStream {
                 ptr: core::ptr::NonNull<u8>,
                 layout: core::alloc::Layout,
                 new_size: usize,
-            ) -> Result<core::ptr::NonNull<u8>, ()> {
+            ) -> Result<core::ptr::NonNull<u8>, #krate::AllocError> {
                 <#ty as #krate::Alloc>::realloc(
                     &mut *Self::_ptr(),
                     ptr,
                     layout,
                     new_size,
                 )
+                .map_err(Self::_error)
+            }
+
+            unsafe fn realloc_excess(
+                &mut self,
+                ptr: core::ptr::NonNull<u8>,
+                layout: core::alloc::Layout,
+                new_size: usize,
+            ) -> Result<#krate::Excess, #krate::AllocError> {
+                <#ty as #krate::Alloc>::realloc_excess(
+                    &mut *Self::_ptr(),
+                    ptr,
+                    layout,
+                    new_size,
+                )
+                .map_err(Self::_error)
             }
         }
     )
     .into()
 }
 
+struct Args {
+    lazy: bool,
+    oom: Option<Path>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let mut lazy = false;
+        let mut oom = None;
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+
+            if ident == "lazy" && !lazy {
+                lazy = true;
+            } else if ident == "oom" && oom.is_none() {
+                let _: Token![=] = input.parse()?;
+                oom = Some(input.parse()?);
+            } else {
+                return Err(parse::Error::new(
+                    ident.span(),
+                    format!("expected `lazy` or `oom = ..`, found `{}`", ident),
+                ));
+            }
+
+            if !input.is_empty() {
+                let _: Token![,] = input.parse()?;
+            }
+        }
+
+        Ok(Args { lazy, oom })
+    }
+}
+
 fn extract_statics(stmts: Vec<Stmt>) -> parse::Result<(Vec<ItemStatic>, Vec<Stmt>)> {
     let mut istmts = stmts.into_iter();
 
     let mut seen = HashSet::new();
     let mut locals = vec![];
     let mut stmts = vec![];
-    while let Some(stmt) = istmts.next() {
+    for stmt in istmts.by_ref() {
         match stmt {
             Stmt::Item(Item::Static(static_)) => {
                 if static_.mutability.is_some() {
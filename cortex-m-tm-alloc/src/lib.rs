@@ -11,7 +11,7 @@
 //!
 //! # Example
 //!
-//! ```
+//! ```ignore
 //! use cortex_m_allocator::allocator;
 //! use cortex_m_rt::{entry, exception};
 //!
@@ -33,6 +33,10 @@
 //!     if let Some(a) = A::get() {
 //!         // `a` has type `A`; `A` implements the `Alloc` and `Copy` traits but NOT the
 //!         // `Send` or `Sync` traits
+//!
+//!         // the allocator itself (`SomeAllocator`) can be accessed using the `with` method; the
+//!         // closure must not use the allocator through `a` (or a copy of it)
+//!         unsafe { a.with(|some_allocator| { /* .. */ }) };
 //!     }
 //! }
 //!
@@ -49,7 +53,10 @@ use core::marker::PhantomData;
 
 /// IMPLEMENTATION DETAIL
 #[doc(hidden)]
-pub use alloc_trait::Alloc;
+pub use alloc_oom::DedicatedHandler;
+/// IMPLEMENTATION DETAIL
+#[doc(hidden)]
+pub use alloc_trait::{Alloc, AllocError, Decision, Excess, Handle, OomInfo};
 pub use cortex_m_tm_alloc_macros::allocator;
 
 /// IMPLEMENTATION DETAIL
@@ -60,6 +67,9 @@ pub struct Private {
 }
 
 impl Private {
+    /// # Safety
+    ///
+    /// This must only be called from the code generated by the `#[allocator]` attribute
     pub unsafe fn get() -> Option<Self> {
         if cfg!(not(cortex_m)) {
             return None;
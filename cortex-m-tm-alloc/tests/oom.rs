@@ -0,0 +1,47 @@
+//! Out-Of-Memory handlers dedicated to an allocator
+
+use std::{alloc::Layout, panic};
+
+use alloc_oom::{oom, record, AllocError, Decision, OomInfo, Operation};
+use alloc_trait::NullAlloc;
+use cortex_m_tm_alloc::allocator;
+
+#[allocator(oom = diverge)]
+static mut A: NullAlloc = NullAlloc;
+
+#[allocator(oom = decide)]
+static mut B: NullAlloc = NullAlloc;
+
+fn diverge(info: OomInfo) -> ! {
+    panic!("A: {}", info)
+}
+
+fn decide(_: OomInfo) -> Decision {
+    Decision::Retry
+}
+
+#[oom]
+fn global(_: OomInfo) -> Decision {
+    Decision::Fail
+}
+
+#[test]
+fn dedicated() {
+    let layout = Layout::from_size_align(24, 8).unwrap();
+    record::clear();
+
+    // a handler that returns a decision is not recorded
+    let info = OomInfo::new(B::_error(AllocError::exhausted(layout)), Operation::Alloc);
+    assert_eq!(alloc_oom::handle(info), Decision::Retry);
+    assert!(record::last().is_none());
+
+    // a diverging one is, before it runs
+    let info = OomInfo::new(A::_error(AllocError::exhausted(layout)), Operation::Alloc);
+    let payload = panic::catch_unwind(|| alloc_oom::handle(info)).unwrap_err();
+    assert!(payload.downcast_ref::<String>().unwrap().starts_with("A: "));
+
+    let record = record::last().unwrap();
+    assert_eq!(record.allocator(), Some("A"));
+    assert_eq!(record.layout(), layout);
+    assert_eq!(record.operation(), Operation::Alloc);
+}
@@ -1,11 +1,12 @@
 use core::{
     alloc::Layout,
     cell::{Cell, UnsafeCell},
-    ops::{Generator, GeneratorState},
+    ops::{Coroutine, CoroutineState},
     pin::Pin,
 };
 
-use alloc_trait::Alloc;
+use alloc_oom::{OomInfo, Operation};
+use alloc_trait::{Alloc, AllocError};
 use collections::Box;
 use heapless::Vec;
 use pin_utils::pin_mut;
@@ -21,6 +22,8 @@ where
     N: heapless::ArrayLength<Pin<Task<A>>>,
 {
     allocator: A,
+    /// Attaches the allocator's name and Out-Of-Memory handler to errors
+    error: fn(AllocError) -> AllocError,
     /// Spawned tasks
     tasks: UnsafeCell<Tasks<A, N>>,
     running: Cell<bool>,
@@ -33,15 +36,16 @@ where
 {
     /// IMPLEMENTATION DETAIL
     #[doc(hidden)]
-    pub fn new(allocator: A) -> Self {
+    pub fn new(allocator: A, error: fn(AllocError) -> AllocError) -> Self {
         Self {
             tasks: UnsafeCell::new(Vec::new()),
             allocator,
+            error,
             running: Cell::new(false),
         }
     }
 
-    pub fn block_on<T>(&self, g: impl Generator<Yield = (), Return = T>) -> T {
+    pub fn block_on<T>(&self, g: impl Coroutine<Yield = (), Return = T>) -> T {
         assert!(!self.running.get());
 
         self.running.set(true);
@@ -50,7 +54,7 @@ where
 
         loop {
             // move forward the main task `g`
-            if let GeneratorState::Complete(x) = g.as_mut().resume() {
+            if let CoroutineState::Complete(x) = g.as_mut().resume(()) {
                 self.running.set(false);
                 break x;
             }
@@ -58,18 +62,18 @@ where
             // since the queue has a fixed capacity and it's stored in a static variable we don't
             // have to worry about pointer invalidation caused by insertion of new tasks *if* we
             // iterator from end to start
-            let n = unsafe { (*self.tasks.get()).len() };
+            let n = unsafe { (&*self.tasks.get()).len() };
             for i in (0..n).rev() {
                 let s = {
                     // this is a (pinned) pointer into the trait object (see `TaskMut` alias above)
-                    // `spawn` calls performed by `task.resume()` won't invalidate this pointer or
+                    // `spawn` calls performed by `task.resume(())` won't invalidate this pointer or
                     // its contents, nor will they alias this reference
                     let task: TaskMut =
-                        unsafe { (*self.tasks.get()).get_unchecked_mut(i).as_mut() };
-                    task.resume()
+                        unsafe { (&mut *self.tasks.get()).get_unchecked_mut(i).as_mut() };
+                    task.resume(())
                 };
 
-                if let GeneratorState::Complete(()) = s {
+                if let CoroutineState::Complete(()) = s {
                     // task completed -- release memory
                     let task = unsafe { (*self.tasks.get()).swap_remove(i) };
                     drop(task);
@@ -78,12 +82,88 @@ where
         }
     }
 
-    pub fn spawn<T>(&self, g: impl Generator<Yield = (), Return = T> + 'static) {
+    pub fn spawn<T>(&self, g: impl Coroutine<Yield = (), Return = T> + 'static) {
         let task: Task<A> = Box::new(GenDrop { g }, self.allocator);
         unsafe {
-            (*self.tasks.get())
-                .push(task.into())
-                .unwrap_or_else(|_| alloc_oom::oom(Layout::new::<Tasks<A, N>>()));
+            (*self.tasks.get()).push(task.into()).unwrap_or_else(|_| {
+                // the queue belongs to the allocator's executor: report it like the allocator's
+                // own errors so the allocator's handler, if any, gets it
+                alloc_oom::oom(OomInfo::new(
+                    (self.error)(AllocError::exhausted(Layout::new::<Tasks<A, N>>())),
+                    Operation::QueueFull,
+                ))
+            });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{
+        ops::{Coroutine, CoroutineState},
+        pin::Pin,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use std::{
+        alloc::System,
+        panic::{self, AssertUnwindSafe},
+        string::String,
+    };
+
+    use alloc_oom::OomInfo;
+    use alloc_trait::Global;
+    use heapless::consts::U1;
+
+    use super::Executor;
+
+    #[alloc_oom::oom]
+    fn oom(info: OomInfo) -> ! {
+        panic!("{}", info)
+    }
+
+    /// Main task that gives the spawned tasks one chance to run
+    struct YieldOnce(bool);
+
+    impl Coroutine for YieldOnce {
+        type Yield = ();
+        type Return = ();
+
+        fn resume(mut self: Pin<&mut Self>, _: ()) -> CoroutineState<(), ()> {
+            if self.0 {
+                CoroutineState::Complete(())
+            } else {
+                self.0 = true;
+                CoroutineState::Yielded(())
+            }
+        }
+    }
+
+    #[test]
+    fn queue_full() {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let executor = Executor::<_, U1>::new(Global(System), |error| error);
+        executor.spawn(
+            #[coroutine]
+            || {
+                RUNS.fetch_add(1, Ordering::Relaxed);
+            },
+        );
+
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| {
+            executor.spawn(
+                #[coroutine]
+                || {},
+            )
+        }))
+        .unwrap_err();
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("executor queue full"), "{}", message);
+
+        // the task that made it into the queue still runs
+        executor.block_on(YieldOnce(false));
+        assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+    }
+}
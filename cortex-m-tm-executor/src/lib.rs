@@ -1,25 +1,25 @@
-#![feature(generator_trait)]
-#![feature(generators)]
+#![cfg_attr(test, feature(coroutines))]
+#![feature(coroutine_trait)]
 #![no_std]
 
 use core::{
     cell::{Cell, UnsafeCell},
     marker::PhantomData,
-    ops::{self, Generator, GeneratorState},
+    ops::{self, Coroutine, CoroutineState},
     pin::Pin,
 };
 
 use alloc_trait::Alloc;
 use collections::{Box, Vec};
-use pin_utils::{pin_mut, unsafe_pinned};
+use pin_utils::pin_mut;
 
 #[doc(hidden)]
 pub use heapless::consts;
 
 pub mod fixed;
 
-type Task<A> = Box<dyn Generator<Yield = (), Return = ()> + 'static, A>;
-type TaskMut<'a> = Pin<&'a mut (dyn Generator<Yield = (), Return = ()> + 'static)>;
+type Task<A> = Box<dyn Coroutine<Yield = (), Return = ()> + 'static, A>;
+type TaskMut<'a> = Pin<&'a mut (dyn Coroutine<Yield = (), Return = ()> + 'static)>;
 
 #[macro_export]
 macro_rules! executor {
@@ -51,11 +51,11 @@ macro_rules! executor {
                 })
             }
 
-            pub fn block_on<T>(&self, g: impl core::ops::Generator<Yield = (), Return = T>) -> T {
+            pub fn block_on<T>(&self, g: impl core::ops::Coroutine<Yield = (), Return = T>) -> T {
                 unsafe { (*Self::_ptr()).block_on(g) }
             }
 
-            pub fn spawn<T>(&self, g: impl core::ops::Generator<Yield = (), Return = T> + 'static) {
+            pub fn spawn<T>(&self, g: impl core::ops::Coroutine<Yield = (), Return = T> + 'static) {
                 unsafe { (*Self::_ptr()).spawn(g) }
             }
 
@@ -89,7 +89,7 @@ macro_rules! executor {
                     if unsafe { !INITIALIZED } {
                         // NOTE this section of code runs exactly once
                         (|| unsafe {
-                            Self::_ptr().write($crate::fixed::Executor::new(a));
+                            Self::_ptr().write($crate::fixed::Executor::new(a, $alloc::_error));
                         })()
                     }
 
@@ -102,11 +102,11 @@ macro_rules! executor {
                 })
             }
 
-            pub fn block_on<T>(&self, g: impl core::ops::Generator<Yield = (), Return = T>) -> T {
+            pub fn block_on<T>(&self, g: impl core::ops::Coroutine<Yield = (), Return = T>) -> T {
                 unsafe { (*Self::_ptr()).block_on(g) }
             }
 
-            pub fn spawn<T>(&self, g: impl core::ops::Generator<Yield = (), Return = T> + 'static) {
+            pub fn spawn<T>(&self, g: impl core::ops::Coroutine<Yield = (), Return = T> + 'static) {
                 unsafe { (*Self::_ptr()).spawn(g) }
             }
 
@@ -145,7 +145,7 @@ where
         }
     }
 
-    pub fn block_on<T>(&self, g: impl Generator<Yield = (), Return = T>) -> T {
+    pub fn block_on<T>(&self, g: impl Coroutine<Yield = (), Return = T>) -> T {
         assert!(!self.running.get());
 
         self.running.set(true);
@@ -154,7 +154,7 @@ where
 
         loop {
             // move forward the main task `g`
-            if let GeneratorState::Complete(x) = g.as_mut().resume() {
+            if let CoroutineState::Complete(x) = g.as_mut().resume(()) {
                 self.running.set(false);
                 break x;
             }
@@ -169,18 +169,18 @@ where
             // The other issue is that a task may append new tasks (using `spawn`). We provide no
             // guarantees about fairness but we'll resume each task *currently* in the list *once*
             // in every pass without any promise about the order in which tasks are resumed.
-            let n = unsafe { (*self.tasks.get()).len() };
+            let n = unsafe { (&*self.tasks.get()).len() };
             for i in (0..n).rev() {
                 let s = {
                     // this is a (pinned) pointer into the trait object (see `TaskMut` alias above)
-                    // `spawn` calls performed by `task.resume()` won't invalidate this pointer or
+                    // `spawn` calls performed by `task.resume(())` won't invalidate this pointer or
                     // its contents, nor will they alias this reference
                     let task: TaskMut =
-                        unsafe { (*self.tasks.get()).get_unchecked_mut(i).as_mut() };
-                    task.resume()
+                        unsafe { (&mut *self.tasks.get()).get_unchecked_mut(i).as_mut() };
+                    task.resume(())
                 };
 
-                if let GeneratorState::Complete(()) = s {
+                if let CoroutineState::Complete(()) = s {
                     // task completed -- release memory
                     let task = unsafe { (*self.tasks.get()).swap_remove(i) };
                     drop(task);
@@ -189,7 +189,7 @@ where
         }
     }
 
-    pub fn spawn<T>(&self, g: impl Generator<Yield = (), Return = T> + 'static) {
+    pub fn spawn<T>(&self, g: impl Coroutine<Yield = (), Return = T> + 'static) {
         // this alternative to `GenDrop` produces larger heap allocations
         // let g = || drop(r#await!(g));
         let task: Task<A> = Box::new(GenDrop { g }, self.allocator);
@@ -205,7 +205,9 @@ struct GenDrop<G> {
 
 impl<G> GenDrop<G> {
     // NOTE trivial projection (I hope)
-    unsafe_pinned!(g: G);
+    fn g(self: Pin<&mut Self>) -> Pin<&mut G> {
+        unsafe { self.map_unchecked_mut(|gd| &mut gd.g) }
+    }
 }
 
 impl<G> ops::Deref for GenDrop<G> {
@@ -222,19 +224,19 @@ impl<G> ops::DerefMut for GenDrop<G> {
     }
 }
 
-impl<G> Generator for GenDrop<G>
+impl<G> Coroutine for GenDrop<G>
 where
-    G: Generator<Yield = ()>,
+    G: Coroutine<Yield = ()>,
 {
     type Yield = ();
     type Return = ();
 
-    fn resume(self: Pin<&mut Self>) -> GeneratorState<(), ()> {
-        match G::resume(self.g()) {
-            GeneratorState::Yielded(()) => GeneratorState::Yielded(()),
-            GeneratorState::Complete(x) => {
+    fn resume(self: Pin<&mut Self>, arg: ()) -> CoroutineState<(), ()> {
+        match G::resume(self.g(), arg) {
+            CoroutineState::Yielded(()) => CoroutineState::Yielded(()),
+            CoroutineState::Complete(x) => {
                 drop(x);
-                GeneratorState::Complete(())
+                CoroutineState::Complete(())
             }
         }
     }
@@ -246,6 +248,9 @@ pub struct Private {
 }
 
 impl Private {
+    /// # Safety
+    ///
+    /// This must only be called from the code generated by the `executor!` macro
     pub unsafe fn new() -> Self {
         Self {
             _not_send_or_sync: PhantomData,
@@ -27,11 +27,11 @@ use panic_semihosting as _;
 use tlsf::Tlsf;
 
 #[allocator(lazy)]
-static mut A: Tlsf = {
+static mut A: Tlsf<4, 8> = {
     static mut MEMORY: [u8; 64] = [0; 64];
 
     let mut tlsf = Tlsf::new();
-    tlsf.extend(MEMORY);
+    tlsf.extend(MEMORY).unwrap();
     tlsf
 };
 
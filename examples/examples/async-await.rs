@@ -1,10 +1,10 @@
 #![deny(warnings)]
-#![feature(generator_trait)]
-#![feature(generators)]
+#![feature(coroutine_trait)]
+#![feature(coroutines)]
 #![no_main]
 #![no_std]
 
-use core::{alloc::Layout, ops::Generator};
+use core::{alloc::Layout, ops::Coroutine};
 
 use alloc_oom::oom;
 use cortex_m_tm_alloc::allocator;
@@ -21,11 +21,11 @@ use panic_semihosting as _;
 use tlsf::Tlsf;
 
 #[allocator(lazy)]
-static mut A: Tlsf = {
+static mut A: Tlsf<4, 8> = {
     static mut MEMORY: [u8; 64] = [0; 64];
 
     let mut tlsf = Tlsf::new();
-    tlsf.extend(MEMORY);
+    tlsf.extend(MEMORY).unwrap();
     tlsf
 };
 
@@ -41,17 +41,23 @@ fn main() -> ! {
     send(p);
 
     if let Some((x, _a)) = X::get() {
-        x.spawn(move || loop {
-            let ret = r#await!(dequeue(c));
-            let _item = ret.0;
-            c = ret.1;
-            // do stuff with `item`
-        });
-
-        x.block_on(|| {
-            // .. do something else ..
-            yield
-        });
+        x.spawn(
+            #[coroutine]
+            move || loop {
+                let ret = r#await!(dequeue(c));
+                let _item = ret.0;
+                c = ret.1;
+                // do stuff with `item`
+            },
+        );
+
+        x.block_on(
+            #[coroutine]
+            || {
+                // .. do something else ..
+                yield
+            },
+        );
     }
 
     debug::exit(debug::EXIT_SUCCESS);
@@ -75,10 +81,11 @@ where
 #[allow(dead_code)]
 fn dequeue2<T, N>(
     mut c: Consumer<'static, T, N>,
-) -> impl Generator<Yield = (), Return = (T, Consumer<'static, T, N>)>
+) -> impl Coroutine<Yield = (), Return = (T, Consumer<'static, T, N>)>
 where
     N: ArrayLength<T>,
 {
+    #[coroutine]
     || loop {
         if let Some(x) = c.dequeue() {
             break (x, c);
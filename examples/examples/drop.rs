@@ -7,8 +7,8 @@
 //! B: I'll destroy the Rc!
 
 #![deny(warnings)]
-#![feature(generator_trait)]
-#![feature(generators)]
+#![feature(coroutine_trait)]
+#![feature(coroutines)]
 #![no_main]
 #![no_std]
 
@@ -24,11 +24,11 @@ use panic_semihosting as _;
 use tlsf::Tlsf;
 
 #[allocator(lazy)]
-static mut A: Tlsf = {
+static mut A: Tlsf<4, 8> = {
     static mut MEMORY: [u8; 128] = [0; 128];
 
     let mut tlsf = Tlsf::new();
-    tlsf.extend(MEMORY);
+    tlsf.extend(MEMORY).unwrap();
     tlsf
 };
 
@@ -40,26 +40,35 @@ fn main() -> ! {
         let a = Rc::new(0, a);
         let b = a.clone();
 
-        x.spawn(move || {
-            if Rc::strong_count(&a) == 1 {
-                hprintln!("A: I'll destroy the Rc!").ok();
-            }
-            drop(a);
-            yield;
-        });
+        x.spawn(
+            #[coroutine]
+            move || {
+                if Rc::strong_count(&a) == 1 {
+                    hprintln!("A: I'll destroy the Rc!").ok();
+                }
+                drop(a);
+                yield;
+            },
+        );
 
-        x.spawn(move || {
-            if Rc::strong_count(&b) == 1 {
-                hprintln!("B: I'll destroy the Rc!").ok();
-            }
-            drop(b);
-            yield;
-        });
+        x.spawn(
+            #[coroutine]
+            move || {
+                if Rc::strong_count(&b) == 1 {
+                    hprintln!("B: I'll destroy the Rc!").ok();
+                }
+                drop(b);
+                yield;
+            },
+        );
 
-        x.block_on(move || {
-            yield;
-            yield;
-        });
+        x.block_on(
+            #[coroutine]
+            move || {
+                yield;
+                yield;
+            },
+        );
     }
 
     debug::exit(debug::EXIT_SUCCESS);
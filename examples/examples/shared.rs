@@ -4,8 +4,8 @@
 //! 2
 
 #![deny(warnings)]
-#![feature(generator_trait)]
-#![feature(generators)]
+#![feature(coroutine_trait)]
+#![feature(coroutines)]
 #![no_main]
 #![no_std]
 
@@ -20,11 +20,11 @@ use panic_semihosting as _;
 use tlsf::Tlsf;
 
 #[allocator(lazy)]
-static mut A: Tlsf = {
+static mut A: Tlsf<4, 8> = {
     static mut MEMORY: [u8; 64] = [0; 64];
 
     let mut tlsf = Tlsf::new();
-    tlsf.extend(MEMORY);
+    tlsf.extend(MEMORY).unwrap();
     tlsf
 };
 
@@ -37,17 +37,23 @@ fn main() -> ! {
     if let Some((x, _a)) = X::get() {
         let shared: &'static _ = SHARED;
 
-        x.spawn(move || loop {
-            hprintln!("{}", shared.borrow()).ok();
-            yield;
-        });
-
-        x.block_on(move || {
-            *shared.borrow_mut() += 1;
-            yield;
-            *shared.borrow_mut() += 1;
-            yield;
-        });
+        x.spawn(
+            #[coroutine]
+            move || loop {
+                hprintln!("{}", shared.borrow()).ok();
+                yield;
+            },
+        );
+
+        x.block_on(
+            #[coroutine]
+            move || {
+                *shared.borrow_mut() += 1;
+                yield;
+                *shared.borrow_mut() += 1;
+                yield;
+            },
+        );
     }
 
     debug::exit(debug::EXIT_SUCCESS);
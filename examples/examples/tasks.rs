@@ -11,8 +11,8 @@
 //! the answer is 42
 
 #![deny(warnings)]
-#![feature(generator_trait)]
-#![feature(generators)]
+#![feature(coroutine_trait)]
+#![feature(coroutines)]
 #![no_main]
 #![no_std]
 
@@ -27,11 +27,11 @@ use panic_semihosting as _;
 use tlsf::Tlsf;
 
 #[allocator(lazy)]
-static mut A: Tlsf = {
+static mut A: Tlsf<4, 8> = {
     static mut MEMORY: [u8; 64] = [0; 64];
 
     let mut tlsf = Tlsf::new();
-    tlsf.extend(MEMORY);
+    tlsf.extend(MEMORY).unwrap();
     tlsf
 };
 
@@ -40,39 +40,48 @@ executor!(name = X, allocator = A);
 #[entry]
 fn main() -> ! {
     if let Some((x, _a)) = X::get() {
-        x.spawn(move || {
-            hprintln!(" A0").ok();
-            yield;
-
-            hprintln!(" A1").ok();
-            // but of course you can `spawn` a task from a spawned task
-            x.spawn(|| {
-                hprintln!("  C0").ok();
+        x.spawn(
+            #[coroutine]
+            move || {
+                hprintln!(" A0").ok();
                 yield;
 
-                hprintln!("  C1").ok();
-            });
-            yield;
+                hprintln!(" A1").ok();
+                // but of course you can `spawn` a task from a spawned task
+                x.spawn(
+                    #[coroutine]
+                    || {
+                        hprintln!("  C0").ok();
+                        yield;
+
+                        hprintln!("  C1").ok();
+                    },
+                );
+                yield;
 
-            hprintln!(" A2").ok();
-            // NOTE return value will be discarded
-            42
-        });
+                hprintln!(" A2").ok();
+                // NOTE return value will be discarded
+                42
+            },
+        );
 
-        let ans = x.block_on(|| {
-            hprintln!("B0").ok();
-            yield;
+        let ans = x.block_on(
+            #[coroutine]
+            || {
+                hprintln!("B0").ok();
+                yield;
 
-            hprintln!("B1").ok();
-            yield;
+                hprintln!("B1").ok();
+                yield;
 
-            hprintln!("B2").ok();
-            yield;
+                hprintln!("B2").ok();
+                yield;
 
-            hprintln!("B3").ok();
+                hprintln!("B3").ok();
 
-            42
-        });
+                42
+            },
+        );
 
         hprintln!("the answer is {}", ans).ok();
     }
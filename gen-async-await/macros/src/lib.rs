@@ -62,9 +62,10 @@ pub fn r#async(args: TokenStream, item: TokenStream) -> TokenStream {
         #(#attrs)*
         #vis fn #ident #generics (
             #inputs
-        ) -> impl core::ops::Generator<Yield = (), Return = #output> #(+ #lts)*
+        ) -> impl core::ops::Coroutine<Yield = (), Return = #output> #(+ #lts)*
         #where_clause
         {
+            #[coroutine]
             move || #block
         }
     )
@@ -1,4 +1,4 @@
-//! `async fn` and `.await` re-implemented as macros on top of `Generator`s
+//! `async fn` and `.await` re-implemented as macros on top of `Coroutine`s
 
 #![deny(missing_docs)]
 #![deny(warnings)]
@@ -19,11 +19,11 @@ macro_rules! r#await {
     ($g:expr) => {
         match $g {
             mut pinned => {
-                use core::ops::Generator;
+                use core::ops::Coroutine;
                 loop {
-                    match unsafe { core::pin::Pin::new_unchecked(&mut pinned).resume() } {
-                        core::ops::GeneratorState::Yielded(()) => {}
-                        core::ops::GeneratorState::Complete(x) => break x,
+                    match unsafe { core::pin::Pin::new_unchecked(&mut pinned).resume(()) } {
+                        core::ops::CoroutineState::Yielded(()) => {}
+                        core::ops::CoroutineState::Complete(x) => break x,
                     }
                     yield ()
                 }
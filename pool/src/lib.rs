@@ -0,0 +1,484 @@
+//! Size-class pool allocator
+//!
+//! The memory is split into a fixed set of size classes; each class is a pool of equally sized
+//! blocks kept in a singly-linked free list. Both `alloc` and `dealloc` run in constant time: they
+//! don't depend on the number of blocks, only on the number of size classes, which is fixed at
+//! compile time.
+//!
+//! This allocator is a good fit for the thread-mode allocator used by `cortex_m_tm_executor` when
+//! the sizes of the spawned generators are known in advance (see the "Hyper-tuning the allocator"
+//! section of the README)
+//!
+//! # Example
+//!
+//! ```ignore
+//! use alloc_trait::NullAlloc;
+//! use cortex_m_tm_alloc::allocator;
+//! use cortex_m_tm_executor::executor;
+//! use pool::{Policy, SizeClass, SizeClassPool, SizeClasses};
+//!
+//! // 4 blocks of 16 bytes and 3 blocks of 48 bytes
+//! const CLASSES: SizeClasses<2> = SizeClasses::new([SizeClass::new(16, 4), SizeClass::new(48, 3)]);
+//!
+//! #[allocator(lazy)]
+//! static mut A: SizeClassPool<NullAlloc, 2> = {
+//!     static mut MEMORY: [u8; 256] = [0; 256];
+//!
+//!     SizeClassPool::new(
+//!         MEMORY,
+//!         CLASSES,
+//!         Policy::Larger,
+//!         // requests that fit no class fail
+//!         NullAlloc,
+//!     )
+//! };
+//!
+//! executor!(name = X, allocator = A);
+//! ```
+
+#![deny(missing_docs)]
+#![deny(warnings)]
+#![no_std]
+
+use core::{alloc::Layout, cmp, mem, ptr::NonNull};
+
+use alloc_trait::{Alloc, AllocError, Excess, Owns};
+
+/// Largest alignment a size class provides
+///
+/// Requests with a larger alignment fit no size class and are forwarded to the fallback allocator
+pub const MAX_ALIGN: usize = 8;
+
+/// A pool of `count` blocks of `size` bytes
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SizeClass {
+    size: usize,
+    count: usize,
+}
+
+impl SizeClass {
+    /// Declares a pool of `count` blocks of `size` bytes
+    ///
+    /// `size` is rounded up to a multiple of the pointer size. The blocks are aligned to the
+    /// largest power of two that divides the rounded size, up to [`MAX_ALIGN`]
+    pub const fn new(size: usize, count: usize) -> Self {
+        Self { size, count }
+    }
+}
+
+/// A validated table of size classes
+///
+/// Create it in a `const` item so that a misconfigured table is rejected at compile time
+///
+/// ```compile_fail
+/// use pool::{SizeClass, SizeClasses};
+///
+/// // the classes are not sorted by size
+/// const CLASSES: SizeClasses<2> =
+///     SizeClasses::new([SizeClass::new(48, 2), SizeClass::new(16, 4)]);
+///
+/// let _ = CLASSES;
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SizeClasses<const N: usize> {
+    classes: [SizeClass; N],
+}
+
+impl<const N: usize> SizeClasses<N> {
+    /// Validates the size `classes` and rounds up their sizes
+    ///
+    /// # Panics
+    ///
+    /// This constructor panics if there are no classes, if a class has no blocks, if the size of
+    /// a class overflows `usize`, or if the classes are not sorted by strictly increasing size
+    /// (after rounding)
+    pub const fn new(mut classes: [SizeClass; N]) -> Self {
+        assert!(N != 0, "there must be at least one size class");
+
+        let word = mem::size_of::<usize>();
+        let mut i = 0;
+        while i < N {
+            let SizeClass { size, count } = classes[i];
+            assert!(count != 0, "size classes must have at least one block");
+
+            // blocks must be able to hold a free list node
+            let size = if size < word { word } else { size };
+            let size = match size.checked_add(word - 1) {
+                Some(size) => size & !(word - 1),
+                None => panic!("size class too large"),
+            };
+            assert!(size.checked_mul(count).is_some(), "size class too large");
+            assert!(
+                i == 0 || classes[i - 1].size < size,
+                "size classes must be sorted by strictly increasing size"
+            );
+
+            classes[i] = SizeClass { size, count };
+            i += 1;
+        }
+
+        Self { classes }
+    }
+}
+
+/// What to do when the size class that fits a request has no free blocks
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Policy {
+    /// Only use the smallest size class that fits the request
+    Exact,
+    /// Try the larger size classes, from smallest to largest
+    Larger,
+}
+
+/// Size-class pool allocator
+///
+/// Requests that fit no size class, and requests that can't be served according to the `Policy`,
+/// are forwarded to the fallback allocator `F`; use `alloc_trait::NullAlloc` to make them fail.
+/// This includes requests aligned to more than [`MAX_ALIGN`] bytes.
+///
+/// Finding the size class of a request, or of a freed block, is a linear scan over the `N`
+/// classes, so `N` should be kept small
+pub struct SizeClassPool<F, const N: usize> {
+    classes: [Class; N],
+    policy: Policy,
+    fallback: F,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Class {
+    size: usize,
+    align: usize,
+    count: usize,
+    start: usize,
+    /// Free list
+    free: Option<NonNull<Free>>,
+    /// Number of blocks that have never been handed out; they sit at the end of the pool
+    fresh: usize,
+}
+
+struct Free {
+    next: Option<NonNull<Free>>,
+}
+
+// NOTE the pool owns the memory it manages (`&'static mut [u8]`)
+unsafe impl<F, const N: usize> Send for SizeClassPool<F, N> where F: Send {}
+
+impl Class {
+    fn fits(&self, layout: &Layout) -> bool {
+        layout.size() <= self.size && layout.align() <= self.align
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.start + self.size * self.count
+    }
+
+    unsafe fn pop(&mut self) -> Option<NonNull<u8>> {
+        if let Some(block) = self.free {
+            self.free = block.as_ref().next;
+            Some(block.cast())
+        } else if self.fresh != 0 {
+            let index = self.count - self.fresh;
+            self.fresh -= 1;
+            Some(NonNull::new_unchecked(
+                (self.start + index * self.size) as *mut u8,
+            ))
+        } else {
+            None
+        }
+    }
+
+    unsafe fn push(&mut self, block: NonNull<u8>) {
+        let block = block.cast::<Free>();
+        block.as_ptr().write(Free { next: self.free });
+        self.free = Some(block);
+    }
+}
+
+impl<F, const N: usize> SizeClassPool<F, N> {
+    /// Carves the size `classes` out of `memory`
+    ///
+    /// # Panics
+    ///
+    /// This constructor panics if `memory` is too small to hold all the size classes
+    pub fn new(
+        memory: &'static mut [u8],
+        classes: SizeClasses<N>,
+        policy: Policy,
+        fallback: F,
+    ) -> Self {
+        let start = memory.as_mut_ptr() as usize;
+        let end = start + memory.len();
+
+        let mut cursor = start;
+        let classes = classes.classes.map(|class| {
+            let size = class.size;
+            let align = cmp::min(size & size.wrapping_neg(), MAX_ALIGN);
+
+            let class_start = (cursor + align - 1) & !(align - 1);
+            cursor = class_start
+                .checked_add(size * class.count)
+                .filter(|&cursor| cursor <= end)
+                .expect("`memory` is too small for the requested size classes");
+
+            Class {
+                size,
+                align,
+                count: class.count,
+                start: class_start,
+                free: None,
+                fresh: class.count,
+            }
+        });
+
+        Self {
+            classes,
+            policy,
+            fallback,
+            start,
+            end,
+        }
+    }
+
+    /// Returns the number of free blocks of the `i`-th (smallest first) size class
+    pub fn free_blocks(&self, i: usize) -> usize {
+        let class = &self.classes[i];
+        let mut n = class.fresh;
+        let mut free = class.free;
+        while let Some(block) = free {
+            n += 1;
+            free = unsafe { block.as_ref().next };
+        }
+        n
+    }
+
+    /// Returns a reference to the fallback allocator
+    pub fn fallback(&self) -> &F {
+        &self.fallback
+    }
+
+    /// Returns a mutable reference to the fallback allocator
+    pub fn fallback_mut(&mut self) -> &mut F {
+        &mut self.fallback
+    }
+
+    fn class_of(&self, ptr: NonNull<u8>) -> Option<usize> {
+        let addr = ptr.as_ptr() as usize;
+        if addr < self.start || addr >= self.end {
+            return None;
+        }
+
+        self.classes.iter().position(|class| class.contains(addr))
+    }
+
+    unsafe fn alloc_block(&mut self, layout: &Layout) -> Option<(NonNull<u8>, usize)> {
+        let first = self.classes.iter().position(|class| class.fits(layout))?;
+
+        let candidates = match self.policy {
+            Policy::Exact => &mut self.classes[first..=first],
+            Policy::Larger => &mut self.classes[first..],
+        };
+
+        candidates
+            .iter_mut()
+            .filter(|class| class.fits(layout))
+            .find_map(|class| class.pop().map(|block| (block, class.size)))
+    }
+}
+
+impl<F, const N: usize> Alloc for SizeClassPool<F, N>
+where
+    F: Alloc,
+{
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.alloc_excess(layout).map(|Excess(ptr, _)| ptr)
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        if let Some((ptr, size)) = self.alloc_block(&layout) {
+            Ok(Excess(ptr, size))
+        } else {
+            self.fallback.alloc_excess(layout)
+        }
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        if let Some(i) = self.class_of(ptr) {
+            self.classes[i].push(ptr)
+        } else {
+            self.fallback.dealloc(ptr, layout)
+        }
+    }
+
+    unsafe fn grow_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        match self.class_of(ptr) {
+            Some(i) if new_size <= self.classes[i].size => Ok(()),
+            Some(_) => Err(AllocError::exhausted(Layout::from_size_align_unchecked(
+                new_size,
+                layout.align(),
+            ))),
+            None => self.fallback.grow_in_place(ptr, layout, new_size),
+        }
+    }
+
+    unsafe fn shrink_in_place(
+        &mut self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        if self.class_of(ptr).is_some() {
+            // the block keeps its size class
+            Ok(())
+        } else {
+            self.fallback.shrink_in_place(ptr, layout, new_size)
+        }
+    }
+}
+
+impl<F, const N: usize> Owns for SizeClassPool<F, N>
+where
+    F: Owns,
+{
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.class_of(ptr).is_some() || self.fallback.owns(ptr, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{alloc::Layout, ptr::NonNull};
+    use std::{alloc::System, boxed::Box, vec};
+
+    use alloc_trait::{Alloc, Excess, Global, NullAlloc};
+
+    use super::{Policy, SizeClass, SizeClassPool, SizeClasses};
+
+    const CLASSES: SizeClasses<2> =
+        SizeClasses::new([SizeClass::new(16, 1), SizeClass::new(48, 2)]);
+
+    fn pool<F>(policy: Policy, fallback: F) -> SizeClassPool<F, 2> {
+        SizeClassPool::new(
+            Box::leak(vec![0; 256].into_boxed_slice()),
+            CLASSES,
+            policy,
+            fallback,
+        )
+    }
+
+    fn in_pool<F>(pool: &SizeClassPool<F, 2>, ptr: NonNull<u8>) -> bool {
+        let addr = ptr.as_ptr() as usize;
+        addr >= pool.start && addr < pool.end
+    }
+
+    #[test]
+    fn exact() {
+        let mut pool = pool(Policy::Exact, NullAlloc);
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let Excess(ptr, size) = pool.alloc_excess(layout).unwrap();
+            assert_eq!(size, 16);
+            assert_eq!(pool.free_blocks(0), 0);
+
+            // the 16-byte class is exhausted; the 48-byte class is not considered
+            assert!(pool.alloc(layout).is_err());
+            assert_eq!(pool.free_blocks(1), 2);
+
+            pool.dealloc(ptr, layout);
+            assert_eq!(pool.free_blocks(0), 1);
+            assert_eq!(pool.alloc(layout).unwrap(), ptr);
+        }
+    }
+
+    #[test]
+    fn larger() {
+        let mut pool = pool(Policy::Larger, NullAlloc);
+        let layout = Layout::new::<u64>();
+
+        unsafe {
+            let Excess(small, size) = pool.alloc_excess(layout).unwrap();
+            assert_eq!(size, 16);
+
+            // the 16-byte class is exhausted so the 48-byte class serves the request
+            let Excess(large, size) = pool.alloc_excess(layout).unwrap();
+            assert_eq!(size, 48);
+            assert_eq!(pool.free_blocks(1), 1);
+
+            pool.alloc(layout).unwrap();
+            assert!(pool.alloc(layout).is_err());
+
+            pool.dealloc(large, layout);
+            pool.dealloc(small, layout);
+            assert_eq!((pool.free_blocks(0), pool.free_blocks(1)), (1, 1));
+        }
+    }
+
+    #[test]
+    fn fallback() {
+        let mut pool = pool(Policy::Exact, Global(System));
+
+        unsafe {
+            // fits no class
+            let big = Layout::from_size_align(64, 8).unwrap();
+            let ptr = pool.alloc(big).unwrap();
+            assert!(!in_pool(&pool, ptr));
+            pool.dealloc(ptr, big);
+
+            // the class that fits is exhausted
+            let small = Layout::new::<u64>();
+            let a = pool.alloc(small).unwrap();
+            let b = pool.alloc(small).unwrap();
+            assert!(in_pool(&pool, a));
+            assert!(!in_pool(&pool, b));
+
+            // frees are routed to the allocator that served the request
+            pool.dealloc(b, small);
+            assert_eq!(pool.free_blocks(0), 0);
+            pool.dealloc(a, small);
+            assert_eq!(pool.free_blocks(0), 1);
+        }
+    }
+
+    #[test]
+    fn rounding() {
+        let classes = SizeClasses::new([SizeClass::new(1, 1), SizeClass::new(12, 1)]);
+        assert_eq!(
+            classes.classes,
+            [SizeClass::new(8, 1), SizeClass::new(16, 1)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "sorted by strictly increasing size")]
+    fn unsorted() {
+        // both round up to 16 bytes
+        SizeClasses::new([SizeClass::new(16, 1), SizeClass::new(12, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one block")]
+    fn empty_class() {
+        SizeClasses::new([SizeClass::new(16, 0)]);
+    }
+
+    #[test]
+    fn overaligned() {
+        let mut pool = pool(Policy::Larger, NullAlloc);
+
+        // 16-byte alignment is larger than `MAX_ALIGN`
+        unsafe {
+            assert!(pool
+                .alloc(Layout::from_size_align(16, 16).unwrap())
+                .is_err())
+        }
+    }
+}
@@ -1,71 +1,1378 @@
-//! Newtype over `japaric/tlsf::Tlsf` to implement the `alloc_trait::Alloc` trait
+//! Two-Level Segregated Fit (TLSF) allocator
 //!
-//! Documentation: https://github.com/japaric/tlsf
+//! Free blocks are kept in segregated free lists indexed by two levels of size classes: the first
+//! level splits sizes into power-of-two ranges and the second level splits each range into `SL`
+//! linear subranges. A pair of bitmaps locates a suitable free list in constant time so `alloc`
+//! and `dealloc` run in bounded time regardless of the size of the request or the state of the
+//! heap; freed blocks are immediately merged with their free neighbors.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use cortex_m_tm_alloc::allocator;
+//! use tlsf::Tlsf;
+//!
+//! #[allocator(lazy)]
+//! static mut A: Tlsf<4, 8> = {
+//!     static mut MEMORY: [u8; 1024] = [0; 1024];
+//!
+//!     let mut tlsf = Tlsf::new();
+//!     tlsf.extend(MEMORY).unwrap();
+//!     tlsf
+//! };
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     if let Some(a) = A::get() {
+//!         // .. use `a` ..
+//!
+//!         let metrics = unsafe { a.with(|tlsf| tlsf.metrics()) };
+//!         hprintln!(
+//!             "{} bytes free in {} blocks; fragmentation: {}",
+//!             metrics.free,
+//!             metrics.free_blocks,
+//!             metrics.fragmentation(),
+//!         )
+//!         .ok();
+//!
+//!         unsafe {
+//!             a.with(|tlsf| {
+//!                 for block in tlsf.blocks() {
+//!                     hprintln!("{:?}", block).ok();
+//!                 }
+//!             })
+//!         };
+//!     }
+//!
+//!     // ..
+//! }
+//! ```
+//!
+//! # Size classes
+//!
+//! `Tlsf<FL, SL>` has `FL` first-level classes and `SL` second-level classes per first-level
+//! class; these are the `FLI` and `2^SLI` parameters of the TLSF paper. Blocks are multiples of
+//! `ALIGN` (two words) and blocks smaller than `SL * ALIGN` bytes have exact-size free lists. The
+//! size classes cover blocks of up to `SL * ALIGN * 2^(FL - 1)` bytes, header included; that's 256
+//! KiB on 32-bit targets with the defaults (`FL = 12`, `SL = 16`). Larger blocks are kept in the
+//! last free list and larger requests are served by a (linear time) first-fit search of that list.
+//! Each first-level class costs `SL + 1` words of allocator state so small heaps are best served by
+//! small `FL` values, e.g. `Tlsf<4, 8>` for a heap of a few hundred bytes.
+//!
+//! # Memory regions
+//!
+//! The heap can span several discontiguous memory regions, e.g. the RAM banks of a
+//! microcontroller. Each region is identified by a [`RegionId`], either chosen by the application
+//! ([`Tlsf::add_region`]) or assigned by [`Tlsf::extend`]. Blocks never span two regions.
+//! [`Tlsf::region_of`] tells which region a block belongs to and [`Tlsf::retire`] removes a
+//! region whose blocks are all free from the heap, returning its memory to the application.
+//!
+//! ```ignore
+//! use cortex_m_tm_alloc::allocator;
+//! use tlsf::{RegionId, Tlsf};
+//!
+//! const SRAM1: RegionId = RegionId(1);
+//! const SRAM2: RegionId = RegionId(2);
+//!
+//! #[allocator(lazy)]
+//! static mut A: Tlsf = {
+//!     #[link_section = ".sram1"]
+//!     static mut SRAM1_HEAP: [u8; 16 * 1024] = [0; 16 * 1024];
+//!     #[link_section = ".sram2"]
+//!     static mut SRAM2_HEAP: [u8; 8 * 1024] = [0; 8 * 1024];
+//!
+//!     let mut tlsf = Tlsf::new();
+//!     tlsf.add_region(SRAM1, SRAM1_HEAP).unwrap();
+//!     tlsf.add_region(SRAM2, SRAM2_HEAP).unwrap();
+//!     tlsf
+//! };
+//!
+//! #[entry]
+//! fn main() -> ! {
+//!     if let Some(a) = A::get() {
+//!         // .. use `a` ..
+//!
+//!         // take SRAM2 back, e.g. to use it as a DMA buffer
+//!         if let Ok(buffer) = unsafe { a.with(|tlsf| tlsf.retire(SRAM2)) } {
+//!             // ..
+//!         }
+//!     }
+//!
+//!     // ..
+//! }
+//! ```
+//!
+//! # Cargo features
+//!
+//! - `validate`: runs [`Tlsf::validate`] after every operation that modifies the heap and panics
+//!   if the heap is corrupted. This makes corruption, e.g. a buffer overrun in `unsafe` code, be
+//!   reported close to where it happened rather than by a later, unrelated, operation. Each
+//!   validation walks the whole heap so this is meant for debugging.
 
+#![deny(missing_docs)]
 #![deny(warnings)]
 #![no_std]
 
-use core::{alloc::Layout, ops, ptr::NonNull};
+use core::{
+    alloc::Layout,
+    cmp, fmt, iter, mem,
+    ptr::{self, NonNull},
+    slice,
+};
+
+use alloc_trait::{Alloc, AllocError, AllocErrorKind, Excess, Owns, Usage};
+
+/// Granularity of block sizes and alignment of the blocks handed out
+const ALIGN: usize = 2 * mem::size_of::<usize>();
+
+/// Size of the header that precedes every block
+const HEADER: usize = 2 * mem::size_of::<usize>();
+
+/// Size of the smallest block: the header plus the free list links
+const MIN_BLOCK: usize = mem::size_of::<Block>();
+
+/// Size of the header placed at the start of every memory region
+const REGION: usize = (mem::size_of::<Region>() + ALIGN - 1) & !(ALIGN - 1);
+
+/// Flag, stored in the lower bits of `Block.size`, that marks a free block
+const FREE: usize = 1;
+
+/// Two-Level Segregated Fit allocator
+///
+/// See the [crate level documentation](index.html) for the meaning of the `FL` and `SL`
+/// parameters
+pub struct Tlsf<const FL: usize = 12, const SL: usize = 16> {
+    /// Bit `i` is set if any of the free lists of first-level class `i` is not empty
+    fl_bitmap: u32,
+    /// Bit `j` of `sl_bitmaps[i]` is set if the free list `(i, j)` is not empty
+    sl_bitmaps: [u32; FL],
+    /// Free lists, indexed by first-level and second-level class
+    heads: [[Option<NonNull<Block>>; SL]; FL],
+    /// Memory regions, in the order they were given to `extend`
+    regions: Option<NonNull<Region>>,
+    /// Number of bytes managed by this allocator, headers included
+    total: usize,
+}
+
+/// Identifier of a memory region
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RegionId(pub u32);
+
+/// Error returned by the operations on memory regions
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegionError {
+    /// The allocator already manages a region with this ID
+    DuplicateId(RegionId),
+    /// The memory is too small to hold a block
+    TooSmall,
+    /// The allocator doesn't manage a region with this ID
+    UnknownId(RegionId),
+    /// Some of the blocks of the region are in use
+    InUse(RegionId),
+    /// `extend` can't assign an ID to the region: the largest ID is in use
+    NoIdLeft,
+}
+
+impl fmt::Display for RegionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RegionError::DuplicateId(id) => write!(f, "region {} already exists", id.0),
+            RegionError::TooSmall => f.write_str("memory region too small to hold a block"),
+            RegionError::UnknownId(id) => write!(f, "unknown region {}", id.0),
+            RegionError::InUse(id) => write!(f, "region {} has blocks in use", id.0),
+            RegionError::NoIdLeft => f.write_str("no region ID left"),
+        }
+    }
+}
+
+/// A block of the heap, as reported by [`Tlsf::blocks`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlockInfo {
+    /// The region the block belongs to
+    pub region: RegionId,
+    /// Address of the first usable byte of the block
+    pub addr: usize,
+    /// Number of usable bytes; this excludes the block header
+    pub size: usize,
+    /// Whether the block is free
+    pub free: bool,
+}
+
+/// Summary of the state of the free memory of a [`Tlsf`] allocator
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Metrics {
+    /// Total number of free bytes, excluding block headers
+    pub free: usize,
+    /// Size, in bytes, of the largest free block; this excludes the block header
+    pub largest_free_block: usize,
+    /// Number of free blocks
+    pub free_blocks: usize,
+}
+
+impl Metrics {
+    /// Returns the fraction of the free memory that can't be used to serve a request of
+    /// `free` bytes: `1 - largest_free_block / free`
+    ///
+    /// This is `0` when all the free memory is in a single block (or when there's no free memory)
+    /// and approaches `1` as the free memory gets split into many small blocks
+    pub fn fragmentation(&self) -> f32 {
+        if self.free == 0 {
+            0.
+        } else {
+            1. - self.largest_free_block as f32 / self.free as f32
+        }
+    }
+}
+
+/// Heap corruption detected by [`Tlsf::validate`]
+///
+/// Blocks are identified by the address of their first usable byte, as in [`BlockInfo::addr`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Corruption {
+    /// The header of a block holds a size that is misaligned, too small or that overruns the
+    /// memory region
+    BadSize {
+        /// Address of the block
+        block: usize,
+        /// The size found in the header, header included
+        size: usize,
+    },
+    /// The header of a block doesn't point to the block that precedes it in memory
+    BadPrevLink {
+        /// Address of the block
+        block: usize,
+    },
+    /// Two adjacent blocks are free; they should have been merged
+    Uncoalesced {
+        /// Address of the first block
+        block: usize,
+        /// Address of the second block
+        next: usize,
+    },
+    /// The last block of a region doesn't end where the region's terminating header starts, or
+    /// that header has been overwritten
+    BadSentinel {
+        /// Address of the start of the region
+        region: usize,
+    },
+    /// A free block is not linked in the free list of its size class
+    NotInFreeList {
+        /// Address of the block
+        block: usize,
+    },
+    /// An entry of a free list is not a free block of the list's size class, or its links are
+    /// inconsistent
+    BadFreeListEntry {
+        /// Address of the entry
+        block: usize,
+        /// First-level class of the list
+        fl: usize,
+        /// Second-level class of the list
+        sl: usize,
+    },
+    /// The bitmaps of a first-level class don't match the state of its free lists
+    BadBitmap {
+        /// The first-level class
+        fl: usize,
+    },
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Corruption::BadSize { block, size } => write!(
+                f,
+                "block at {:#x} has an invalid size ({} bytes)",
+                block, size
+            ),
+            Corruption::BadPrevLink { block } => write!(
+                f,
+                "block at {:#x} doesn't point to the block that precedes it",
+                block
+            ),
+            Corruption::Uncoalesced { block, next } => write!(
+                f,
+                "free blocks at {:#x} and {:#x} are adjacent but were not merged",
+                block, next
+            ),
+            Corruption::BadSentinel { region } => write!(
+                f,
+                "the end of the region that starts at {:#x} has been overwritten",
+                region
+            ),
+            Corruption::NotInFreeList { block } => {
+                write!(f, "free block at {:#x} is not in its free list", block)
+            }
+            Corruption::BadFreeListEntry { block, fl, sl } => write!(
+                f,
+                "entry at {:#x} of free list ({}, {}) is not a free block of that size class",
+                block, fl, sl
+            ),
+            Corruption::BadBitmap { fl } => write!(
+                f,
+                "bitmaps of first-level class {} don't match its free lists",
+                fl
+            ),
+        }
+    }
+}
+
+/// Header placed at the start of every memory region
+#[repr(C)]
+struct Region {
+    next: Option<NonNull<Region>>,
+    id: RegionId,
+    /// Size of the region, this header and the sentinel included
+    len: usize,
+}
 
-pub struct Tlsf {
-    inner: tlsf::Tlsf,
+/// Header of a block; the free list links are only present in free blocks
+#[repr(C)]
+struct Block {
+    /// The block that precedes this one in memory; `None` for the first block of a region
+    prev_phys: Option<NonNull<Block>>,
+    /// Size of the block, header included; the lower bits are flags
+    size: usize,
+    next_free: Option<NonNull<Block>>,
+    prev_free: Option<NonNull<Block>>,
 }
 
-impl Tlsf {
+// NOTE `Tlsf` owns the memory it manages (`&'static mut [u8]`) so it can be moved to a different
+// execution context; this lets it be used as a `#[global_allocator]` through `alloc_trait::Locked`
+unsafe impl<const FL: usize, const SL: usize> Send for Tlsf<FL, SL> {}
+
+impl<const FL: usize, const SL: usize> Tlsf<FL, SL> {
+    const SL_LOG2: u32 = SL.trailing_zeros();
+    /// log2 of the size of the smallest block of first-level class 1
+    const FL_SHIFT: u32 = Self::SL_LOG2 + ALIGN.trailing_zeros();
+
+    /// Creates an allocator that manages no memory; give it memory with [`Tlsf::extend`]
+    ///
+    /// # Panics
+    ///
+    /// This constructor panics if `SL` is not a power of two in the range `2..=32` or if `FL` is
+    /// not in the range `1..=32`
     pub const fn new() -> Self {
+        assert!(
+            SL.is_power_of_two() && SL >= 2 && SL <= 32,
+            "`SL` must be a power of two in the range `2..=32`"
+        );
+        assert!(FL >= 1 && FL <= 32, "`FL` must be in the range `1..=32`");
+
         Self {
-            inner: tlsf::Tlsf::new(),
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL],
+            heads: [[None; SL]; FL],
+            regions: None,
+            total: 0,
         }
     }
-}
 
-impl ops::Deref for Tlsf {
-    type Target = tlsf::Tlsf;
+    /// Gives the allocator more `memory` to manage, as a new region
+    ///
+    /// The region is assigned the ID that follows the largest one in use, or `RegionId(0)` if this
+    /// is the first region, and that ID is returned. This fails, and the memory is ignored, if the
+    /// memory is too small to hold a block or if the largest ID in use is `RegionId(u32::MAX)`
+    pub fn extend(&mut self, memory: &'static mut [u8]) -> Result<RegionId, RegionError> {
+        let id = match self
+            .regions()
+            .map(|region| unsafe { region.as_ref().id.0 })
+            .max()
+        {
+            Some(id) => RegionId(id.checked_add(1).ok_or(RegionError::NoIdLeft)?),
+            None => RegionId(0),
+        };
 
-    fn deref(&self) -> &tlsf::Tlsf {
-        &self.inner
+        self.add_region(id, memory).map(|_| id)
+    }
+
+    /// Gives the allocator more `memory` to manage, as a new region with the given `id`
+    ///
+    /// Regions don't need to be contiguous; blocks never span two regions
+    pub fn add_region(
+        &mut self,
+        id: RegionId,
+        memory: &'static mut [u8],
+    ) -> Result<(), RegionError> {
+        if self.find_region(id).is_some() {
+            return Err(RegionError::DuplicateId(id));
+        }
+
+        let raw = memory.as_mut_ptr() as usize;
+        let start = match raw.checked_add(ALIGN - 1) {
+            Some(addr) => addr & !(ALIGN - 1),
+            None => return Err(RegionError::TooSmall),
+        };
+        let end = (raw + memory.len()) & !(ALIGN - 1);
+
+        // the region starts with a `Region` header and ends with a zero-sized "used" block (the
+        // sentinel) that prevents merging the last block with whatever follows the region
+        if end < start || end - start < REGION + MIN_BLOCK + HEADER {
+            return Err(RegionError::TooSmall);
+        }
+        let size = end - HEADER - REGION - start;
+
+        unsafe {
+            let region = start as *mut Region;
+            region.write(Region {
+                next: None,
+                id,
+                len: end - start,
+            });
+            let region = NonNull::new_unchecked(region);
+            let mut link = &mut self.regions;
+            while let Some(region) = link {
+                link = &mut region.as_mut().next;
+            }
+            *link = Some(region);
+
+            let block = first_block(region).as_ptr();
+            block.write(Block {
+                prev_phys: None,
+                size: size | FREE,
+                next_free: None,
+                prev_free: None,
+            });
+            let block = NonNull::new_unchecked(block);
+
+            // NOTE the sentinel only has room for the header
+            let sentinel = next_phys(block).as_ptr();
+            ptr::addr_of_mut!((*sentinel).prev_phys).write(Some(block));
+            ptr::addr_of_mut!((*sentinel).size).write(0);
+
+            self.insert(block);
+        }
+
+        self.total += end - start;
+        self.debug_validate();
+
+        Ok(())
+    }
+
+    /// Returns the ID of the region that contains the block at `ptr`
+    ///
+    /// Returns `None` if `ptr` doesn't point into memory managed by this allocator
+    pub fn region_of(&self, ptr: NonNull<u8>) -> Option<RegionId> {
+        let addr = ptr.as_ptr() as usize;
+        self.regions()
+            .find(|region| unsafe {
+                let start = region.as_ptr() as usize;
+                addr >= start + REGION && addr < start + region.as_ref().len - HEADER
+            })
+            .map(|region| unsafe { region.as_ref().id })
+    }
+
+    /// Removes the region `id` from the heap and returns its memory
+    ///
+    /// This fails if any of the blocks of the region is in use. The returned memory may be
+    /// slightly smaller than the memory given to `add_region` (or `extend`) as it excludes the
+    /// bytes that were skipped to align the region
+    pub fn retire(&mut self, id: RegionId) -> Result<&'static mut [u8], RegionError> {
+        let region = self.find_region(id).ok_or(RegionError::UnknownId(id))?;
+
+        unsafe {
+            // a region whose blocks are all free consists of a single free block
+            let block = first_block(region);
+            if !is_free(block) || size(next_phys(block)) != 0 {
+                return Err(RegionError::InUse(id));
+            }
+
+            self.remove(block);
+
+            let mut link = &mut self.regions;
+            while let Some(mut current) = *link {
+                if current == region {
+                    *link = current.as_ref().next;
+                    break;
+                }
+                link = &mut current.as_mut().next;
+            }
+
+            let len = region.as_ref().len;
+            self.total -= len;
+            self.debug_validate();
+
+            Ok(slice::from_raw_parts_mut(region.as_ptr() as *mut u8, len))
+        }
+    }
+
+    fn regions(&self) -> impl Iterator<Item = NonNull<Region>> + '_ {
+        let mut next = self.regions;
+        iter::from_fn(move || {
+            let region = next?;
+            next = unsafe { region.as_ref().next };
+            Some(region)
+        })
+    }
+
+    fn find_region(&self, id: RegionId) -> Option<NonNull<Region>> {
+        self.regions()
+            .find(|region| unsafe { region.as_ref().id == id })
+    }
+
+    /// Size of the block that serves `size` bytes, header included
+    fn block_size(size: usize) -> Option<usize> {
+        let size = size.checked_add(HEADER + ALIGN - 1)? & !(ALIGN - 1);
+        Some(cmp::max(size, MIN_BLOCK))
+    }
+
+    /// Size classes of blocks of the given `size`; `None` if the size is beyond the last class
+    fn class(size: usize) -> Option<(usize, usize)> {
+        if size < 1 << Self::FL_SHIFT {
+            Some((0, size / ALIGN))
+        } else {
+            let msb = usize::BITS - 1 - size.leading_zeros();
+            let fl = (msb - Self::FL_SHIFT + 1) as usize;
+            if fl < FL {
+                Some((fl, (size >> (msb - Self::SL_LOG2)) - SL))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Size classes of the free list that holds blocks of the given `size`
+    fn mapping(size: usize) -> (usize, usize) {
+        Self::class(size).unwrap_or((FL - 1, SL - 1))
+    }
+
+    /// Finds a free block of at least `size` bytes
+    fn find(&self, size: usize) -> Option<NonNull<Block>> {
+        // round up the size to the next class boundary: all the blocks of that class, and of the
+        // classes above it, are large enough
+        let rounded = if size < 1 << Self::FL_SHIFT {
+            Some(size)
+        } else {
+            let msb = usize::BITS - 1 - size.leading_zeros();
+            size.checked_add((1 << (msb - Self::SL_LOG2)) - 1)
+        };
+
+        let (fl, sl) = match rounded.and_then(Self::class) {
+            Some(class) => class,
+            None => {
+                // larger than the size classes: first fit
+                let mut next = self.heads[FL - 1][SL - 1];
+                while let Some(block) = next {
+                    if unsafe { self::size(block) } >= size {
+                        return Some(block);
+                    }
+                    next = unsafe { block.as_ref().next_free };
+                }
+                return None;
+            }
+        };
+
+        let sl_map = self.sl_bitmaps[fl] & (!0 << sl);
+        let (fl, sl_map) = if sl_map != 0 {
+            (fl, sl_map)
+        } else {
+            let fl_map = self.fl_bitmap & (!0u32).checked_shl(fl as u32 + 1).unwrap_or(0);
+            if fl_map == 0 {
+                return None;
+            }
+
+            let fl = fl_map.trailing_zeros() as usize;
+            (fl, self.sl_bitmaps[fl])
+        };
+
+        self.heads[fl][sl_map.trailing_zeros() as usize]
+    }
+
+    /// Pushes the free `block` onto its free list
+    unsafe fn insert(&mut self, mut block: NonNull<Block>) {
+        let (fl, sl) = Self::mapping(size(block));
+
+        let head = self.heads[fl][sl];
+        block.as_mut().next_free = head;
+        block.as_mut().prev_free = None;
+        if let Some(mut head) = head {
+            head.as_mut().prev_free = Some(block);
+        }
+
+        self.heads[fl][sl] = Some(block);
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl] |= 1 << sl;
+    }
+
+    /// Unlinks the free `block` from its free list
+    unsafe fn remove(&mut self, block: NonNull<Block>) {
+        let (fl, sl) = Self::mapping(size(block));
+        let Block {
+            next_free,
+            prev_free,
+            ..
+        } = *block.as_ptr();
+
+        if let Some(mut next) = next_free {
+            next.as_mut().prev_free = prev_free;
+        }
+        match prev_free {
+            Some(mut prev) => prev.as_mut().next_free = next_free,
+            None => {
+                self.heads[fl][sl] = next_free;
+                if next_free.is_none() {
+                    self.sl_bitmaps[fl] &= !(1 << sl);
+                    if self.sl_bitmaps[fl] == 0 {
+                        self.fl_bitmap &= !(1 << fl);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Shrinks the used `block` to `size` bytes and releases the tail, if it can hold a block
+    unsafe fn trim(&mut self, mut block: NonNull<Block>, size: usize) {
+        let rest_size = self::size(block) - size;
+        if rest_size < MIN_BLOCK {
+            return;
+        }
+
+        let mut next = next_phys(block);
+        let rest = (block.as_ptr() as usize + size) as *mut Block;
+        rest.write(Block {
+            prev_phys: Some(block),
+            size: rest_size,
+            next_free: None,
+            prev_free: None,
+        });
+        let rest = NonNull::new_unchecked(rest);
+        next.as_mut().prev_phys = Some(rest);
+        block.as_mut().size = size;
+
+        self.release(rest)
+    }
+
+    /// Marks `block` as free, merges it with its free neighbors and pushes the result onto its
+    /// free list
+    unsafe fn release(&mut self, mut block: NonNull<Block>) {
+        // merge with the next block
+        let next = next_phys(block);
+        if is_free(next) {
+            self.remove(next);
+            self.absorb(block, next);
+        }
+
+        // merge with the previous block
+        if let Some(prev) = block.as_ref().prev_phys {
+            if is_free(prev) {
+                self.remove(prev);
+                self.absorb(prev, block);
+                block = prev;
+            }
+        }
+
+        block.as_mut().size = size(block) | FREE;
+        self.insert(block)
+    }
+
+    /// Merges `next`, the block that follows `block` in memory, into `block`
+    unsafe fn absorb(&mut self, mut block: NonNull<Block>, next: NonNull<Block>) {
+        block.as_mut().size += size(next);
+        next_phys(block).as_mut().prev_phys = Some(block);
+    }
+
+    /// Returns an iterator over all the blocks of the heap, free and used, in address order within
+    /// each memory region
+    pub fn blocks(&self) -> impl Iterator<Item = BlockInfo> + '_ {
+        let mut regions = self.regions();
+        let mut region = RegionId(0);
+        let mut next: Option<NonNull<Block>> = None;
+        iter::from_fn(move || unsafe {
+            loop {
+                let block = match next {
+                    Some(block) => block,
+                    None => {
+                        let current = regions.next()?;
+                        region = current.as_ref().id;
+                        first_block(current)
+                    }
+                };
+
+                if size(block) == 0 {
+                    // sentinel: move on to the next region
+                    next = None;
+                    continue;
+                }
+
+                next = Some(next_phys(block));
+                return Some(BlockInfo {
+                    region,
+                    addr: payload(block).as_ptr() as usize,
+                    size: size(block) - HEADER,
+                    free: is_free(block),
+                });
+            }
+        })
+    }
+
+    /// Returns the free memory metrics
+    ///
+    /// This walks the free lists so it runs in time proportional to the number of free blocks
+    pub fn metrics(&self) -> Metrics {
+        let mut metrics = Metrics {
+            free: 0,
+            largest_free_block: 0,
+            free_blocks: 0,
+        };
+        for head in self.heads.iter().flat_map(|heads| heads.iter()) {
+            let mut next = *head;
+            while let Some(block) = next {
+                let size = unsafe { size(block) } - HEADER;
+                metrics.free += size;
+                metrics.largest_free_block = cmp::max(metrics.largest_free_block, size);
+                metrics.free_blocks += 1;
+                next = unsafe { block.as_ref().next_free };
+            }
+        }
+
+        metrics
+    }
+
+    /// Checks the integrity of the heap
+    ///
+    /// This checks the header of every block, the links between neighboring blocks, that no two
+    /// adjacent blocks are free, that every free block is in the right free list, and that nothing
+    /// else is, and that the bitmaps match the free lists. The first problem found is returned.
+    ///
+    /// This walks the whole heap and, in the worst case, runs in time proportional to the square
+    /// of the number of free blocks
+    pub fn validate(&self) -> Result<(), Corruption> {
+        unsafe {
+            // physical blocks
+            let mut free_blocks = 0;
+            for current in self.regions() {
+                let sentinel = current.as_ptr() as usize + current.as_ref().len - HEADER;
+
+                let mut prev: Option<NonNull<Block>> = None;
+                let mut block = first_block(current);
+                while (block.as_ptr() as usize) < sentinel {
+                    let addr = payload(block).as_ptr() as usize;
+                    let size = size(block);
+
+                    if block.as_ref().prev_phys != prev {
+                        return Err(Corruption::BadPrevLink { block: addr });
+                    }
+
+                    if size < MIN_BLOCK
+                        || size & (ALIGN - 1) != 0
+                        || size > sentinel - block.as_ptr() as usize
+                    {
+                        return Err(Corruption::BadSize { block: addr, size });
+                    }
+
+                    if is_free(block) {
+                        if let Some(prev) = prev.filter(|prev| is_free(*prev)) {
+                            return Err(Corruption::Uncoalesced {
+                                block: payload(prev).as_ptr() as usize,
+                                next: addr,
+                            });
+                        }
+
+                        let linked = match block.as_ref().prev_free {
+                            None => {
+                                let (fl, sl) = Self::mapping(size);
+                                self.heads[fl][sl] == Some(block)
+                            }
+                            Some(prev_free) => {
+                                self.in_heap(prev_free)
+                                    && prev_free.as_ref().next_free == Some(block)
+                            }
+                        };
+                        if !linked {
+                            return Err(Corruption::NotInFreeList { block: addr });
+                        }
+
+                        free_blocks += 1;
+                    }
+
+                    prev = Some(block);
+                    block = next_phys(block);
+                }
+
+                if block.as_ptr() as usize != sentinel
+                    || block.as_ref().size != 0
+                    || block.as_ref().prev_phys != prev
+                {
+                    return Err(Corruption::BadSentinel {
+                        region: current.as_ptr() as usize,
+                    });
+                }
+            }
+
+            // free lists and bitmaps
+            let mut listed = 0;
+            for fl in 0..FL {
+                for sl in 0..SL {
+                    let head = self.heads[fl][sl];
+                    if head.is_some() != (self.sl_bitmaps[fl] & (1 << sl) != 0) {
+                        return Err(Corruption::BadBitmap { fl });
+                    }
+
+                    let mut prev = None;
+                    let mut next = head;
+                    while let Some(block) = next {
+                        // NOTE `listed` also bounds the walk if the list has a cycle
+                        listed += 1;
+                        if !self.in_heap(block)
+                            || !is_free(block)
+                            || Self::mapping(size(block)) != (fl, sl)
+                            || block.as_ref().prev_free != prev
+                            || listed > free_blocks
+                        {
+                            return Err(Corruption::BadFreeListEntry {
+                                block: payload(block).as_ptr() as usize,
+                                fl,
+                                sl,
+                            });
+                        }
+
+                        prev = Some(block);
+                        next = block.as_ref().next_free;
+                    }
+                }
+
+                if (self.fl_bitmap & (1 << fl) != 0) != (self.sl_bitmaps[fl] != 0)
+                    || self.sl_bitmaps[fl].checked_shr(SL as u32).unwrap_or(0) != 0
+                {
+                    return Err(Corruption::BadBitmap { fl });
+                }
+            }
+
+            if listed != free_blocks {
+                // some free block is not reachable from the head of its free list
+                let unlisted = self.blocks().find(|block| {
+                    block.free && {
+                        let block = header(NonNull::new_unchecked(block.addr as *mut u8));
+                        let (fl, sl) = Self::mapping(size(block));
+                        let mut next = self.heads[fl][sl];
+                        while let Some(entry) = next {
+                            if entry == block {
+                                return false;
+                            }
+                            next = entry.as_ref().next_free;
+                        }
+                        true
+                    }
+                });
+
+                if let Some(block) = unlisted {
+                    return Err(Corruption::NotInFreeList { block: block.addr });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the header of a free block at `block` would lie within one of the memory
+    /// regions
+    fn in_heap(&self, block: NonNull<Block>) -> bool {
+        let addr = block.as_ptr() as usize;
+        addr & (ALIGN - 1) == 0
+            && self.regions().any(|region| {
+                let start = region.as_ptr() as usize;
+                let len = unsafe { region.as_ref().len };
+                addr >= start + REGION && addr + MIN_BLOCK <= start + len - HEADER
+            })
+    }
+
+    /// Runs [`Tlsf::validate`] when the `validate` feature is enabled; panics if it fails
+    fn debug_validate(&self) {
+        if cfg!(feature = "validate") {
+            if let Err(corruption) = self.validate() {
+                panic!("TLSF heap corrupted: {}", corruption)
+            }
+        }
+    }
+
+    fn usage(&self) -> Usage {
+        let metrics = self.metrics();
+        Usage {
+            free: metrics.free,
+            used: self.total - metrics.free,
+            largest_free_block: metrics.largest_free_block,
+        }
     }
 }
 
-impl ops::DerefMut for Tlsf {
-    fn deref_mut(&mut self) -> &mut tlsf::Tlsf {
-        &mut self.inner
+impl<const FL: usize, const SL: usize> Default for Tlsf<FL, SL> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl alloc_trait::Alloc for Tlsf {
-    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, ()> {
-        self.inner.alloc(layout)
+impl<const FL: usize, const SL: usize> Alloc for Tlsf<FL, SL> {
+    unsafe fn alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.alloc_excess(layout).map(|Excess(ptr, _)| ptr)
+    }
+
+    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, _layout: Layout) {
+        self.release(header(ptr));
+        self.debug_validate()
     }
 
-    unsafe fn dealloc(&mut self, ptr: NonNull<u8>, _layout: core::alloc::Layout) {
-        self.inner.dealloc(ptr)
+    fn usable_size(&self, layout: &Layout) -> (usize, usize) {
+        match Self::block_size(layout.size()) {
+            Some(size) => (layout.size(), size - HEADER),
+            None => (layout.size(), layout.size()),
+        }
+    }
+
+    unsafe fn alloc_excess(&mut self, layout: Layout) -> Result<Excess, AllocError> {
+        let size = match Self::block_size(layout.size()) {
+            Some(size) => size,
+            None => return Err(AllocError::new(layout, AllocErrorKind::SizeOverflow)),
+        };
+        // over-allocate so that the block can be moved forward to an aligned address while
+        // leaving room for a free block in front of it
+        let search = if layout.align() <= ALIGN {
+            Some(size)
+        } else {
+            size.checked_add(layout.align() + MIN_BLOCK)
+        };
+        let search = match search {
+            Some(search) => search,
+            None => return Err(AllocError::new(layout, AllocErrorKind::SizeOverflow)),
+        };
+
+        let mut block = match self.find(search) {
+            Some(block) => block,
+            None => return Err(AllocError::exhausted(layout).with_usage(self.usage())),
+        };
+        self.remove(block);
+        block.as_mut().size = self::size(block);
+
+        if layout.align() > ALIGN {
+            let addr = block.as_ptr() as usize;
+            let mut payload = align_up(addr + HEADER, layout.align());
+            if payload - HEADER != addr && payload - HEADER - addr < MIN_BLOCK {
+                payload = align_up(addr + HEADER + MIN_BLOCK, layout.align());
+            }
+
+            let gap = payload - HEADER - addr;
+            if gap != 0 {
+                // split off the gap as a free block
+                let mut next = next_phys(block);
+                let aligned = (payload - HEADER) as *mut Block;
+                aligned.write(Block {
+                    prev_phys: Some(block),
+                    size: self::size(block) - gap,
+                    next_free: None,
+                    prev_free: None,
+                });
+                let aligned = NonNull::new_unchecked(aligned);
+                next.as_mut().prev_phys = Some(aligned);
+                block.as_mut().size = gap;
+
+                self.release(block);
+                block = aligned;
+            }
+        }
+
+        self.trim(block, size);
+        self.debug_validate();
+
+        Ok(Excess(payload(block), self::size(block) - HEADER))
     }
 
     unsafe fn grow_in_place(
         &mut self,
         ptr: NonNull<u8>,
-        _layout: Layout,
+        layout: Layout,
         new_size: usize,
-    ) -> Result<(), ()> {
-        self.inner.grow_in_place(ptr, new_size)
+    ) -> Result<(), AllocError> {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let size = match Self::block_size(new_size) {
+            Some(size) => size,
+            None => return Err(AllocError::new(new_layout, AllocErrorKind::SizeOverflow)),
+        };
+        let block = header(ptr);
+
+        if self::size(block) >= size {
+            return Ok(());
+        }
+
+        let next = next_phys(block);
+        if !is_free(next) || self::size(block) + self::size(next) < size {
+            return Err(AllocError::exhausted(new_layout).with_usage(self.usage()));
+        }
+
+        self.remove(next);
+        self.absorb(block, next);
+        self.trim(block, size);
+        self.debug_validate();
+
+        Ok(())
     }
 
     unsafe fn shrink_in_place(
         &mut self,
         ptr: NonNull<u8>,
-        _layout: Layout,
+        layout: Layout,
         new_size: usize,
-    ) -> Result<(), ()> {
-        self.inner.shrink_in_place(ptr, new_size)
+    ) -> Result<(), AllocError> {
+        let size = match Self::block_size(new_size) {
+            Some(size) => size,
+            None => {
+                return Err(AllocError::new(
+                    Layout::from_size_align_unchecked(new_size, layout.align()),
+                    AllocErrorKind::SizeOverflow,
+                ))
+            }
+        };
+
+        self.trim(header(ptr), size);
+        self.debug_validate();
+
+        Ok(())
     }
+}
 
-    unsafe fn realloc(
-        &mut self,
-        ptr: NonNull<u8>,
-        layout: Layout,
-        new_size: usize,
-    ) -> Result<NonNull<u8>, ()> {
-        self.inner.realloc(ptr, layout, new_size)
+impl<const FL: usize, const SL: usize> Owns for Tlsf<FL, SL> {
+    fn owns(&self, ptr: NonNull<u8>, _layout: Layout) -> bool {
+        self.region_of(ptr).is_some()
+    }
+}
+
+unsafe fn first_block(region: NonNull<Region>) -> NonNull<Block> {
+    NonNull::new_unchecked((region.as_ptr() as usize + REGION) as *mut Block)
+}
+
+unsafe fn size(block: NonNull<Block>) -> usize {
+    block.as_ref().size & !FREE
+}
+
+unsafe fn is_free(block: NonNull<Block>) -> bool {
+    block.as_ref().size & FREE != 0
+}
+
+unsafe fn next_phys(block: NonNull<Block>) -> NonNull<Block> {
+    NonNull::new_unchecked((block.as_ptr() as usize + size(block)) as *mut Block)
+}
+
+unsafe fn header(ptr: NonNull<u8>) -> NonNull<Block> {
+    NonNull::new_unchecked((ptr.as_ptr() as usize - HEADER) as *mut Block)
+}
+
+unsafe fn payload(block: NonNull<Block>) -> NonNull<u8> {
+    NonNull::new_unchecked((block.as_ptr() as usize + HEADER) as *mut u8)
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{alloc::Layout, mem, ptr::NonNull};
+    use std::{boxed::Box, vec, vec::Vec};
+
+    use alloc_trait::{Alloc, Allocator, Excess};
+
+    use super::{
+        header, BlockInfo, Corruption, Metrics, RegionError, RegionId, Tlsf, ALIGN, HEADER,
+    };
+
+    fn memory(len: usize) -> &'static mut [u8] {
+        Box::leak(vec![0; len].into_boxed_slice())
+    }
+
+    #[test]
+    fn alloc_excess() {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        tlsf.extend(memory(512)).unwrap();
+
+        for size in 1..=3 * mem::size_of::<usize>() {
+            let layout = Layout::from_size_align(size, 1).unwrap();
+            let (min, max) = tlsf.usable_size(&layout);
+            assert_eq!(min, size);
+            assert!(max >= size);
+
+            unsafe {
+                let Excess(ptr, excess) = tlsf.alloc_excess(layout).unwrap();
+                assert!(excess >= max);
+                tlsf.dealloc(ptr, layout);
+            }
+        }
+
+        // the rounding is visible to `Allocator` users, e.g. `collections::Vec` sizes its capacity
+        // after the length of the returned block
+        let layout = Layout::new::<u8>();
+        let (_, max) = tlsf.usable_size(&layout);
+        assert!(max > 1);
+        let block = tlsf.allocate(layout).unwrap();
+        assert!(block.len() >= max);
+        unsafe { tlsf.deallocate(block.cast(), layout) }
+    }
+
+    #[test]
+    fn metrics_and_blocks() {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        let region = tlsf.extend(memory(512)).unwrap();
+
+        let initial = tlsf.metrics();
+        assert_eq!(initial.free_blocks, 1);
+        assert_eq!(initial.largest_free_block, initial.free);
+        assert_eq!(initial.fragmentation(), 0.);
+        let initial_blocks = tlsf.blocks().collect::<Vec<_>>();
+        assert_eq!(
+            initial_blocks,
+            [BlockInfo {
+                region,
+                addr: initial_blocks[0].addr,
+                size: initial.free,
+                free: true,
+            }]
+        );
+
+        // allocate three blocks and free the first and the last one: the first one can't be merged
+        // with its neighbors
+        let layout = Layout::from_size_align(32, ALIGN).unwrap();
+        let (a, b, c) = unsafe {
+            (
+                tlsf.alloc_excess(layout).unwrap(),
+                tlsf.alloc_excess(layout).unwrap(),
+                tlsf.alloc_excess(layout).unwrap(),
+            )
+        };
+        unsafe {
+            tlsf.dealloc(a.0, layout);
+            tlsf.dealloc(c.0, layout);
+        }
+
+        let rest = initial.free - a.1 - b.1 - 2 * HEADER;
+        let blocks = tlsf.blocks().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks.contains(&BlockInfo {
+            region,
+            addr: a.0.as_ptr() as usize,
+            size: a.1,
+            free: true,
+        }));
+        assert!(blocks.contains(&BlockInfo {
+            region,
+            addr: b.0.as_ptr() as usize,
+            size: b.1,
+            free: false,
+        }));
+        assert!(blocks.iter().any(|block| block.free && block.size == rest));
+
+        let metrics = tlsf.metrics();
+        assert_eq!(
+            metrics,
+            Metrics {
+                free: a.1 + rest,
+                largest_free_block: rest,
+                free_blocks: 2,
+            }
+        );
+        assert!(metrics.fragmentation() > 0.);
+        assert!(metrics.fragmentation() < 1.);
+
+        // everything is merged back into a single block
+        unsafe { tlsf.dealloc(b.0, layout) }
+        assert_eq!(tlsf.metrics(), initial);
+        assert_eq!(tlsf.blocks().collect::<Vec<_>>(), initial_blocks);
+    }
+
+    #[test]
+    fn fragmentation() {
+        let metrics = |free, largest_free_block, free_blocks| Metrics {
+            free,
+            largest_free_block,
+            free_blocks,
+        };
+
+        // no free memory
+        assert_eq!(metrics(0, 0, 0).fragmentation(), 0.);
+        // a single free block
+        assert_eq!(metrics(256, 256, 1).fragmentation(), 0.);
+        // the free memory is split into four equally sized blocks
+        assert_eq!(metrics(256, 64, 4).fragmentation(), 0.75);
+    }
+
+    #[test]
+    fn validate() {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        tlsf.extend(memory(1024)).unwrap();
+        assert_eq!(tlsf.validate(), Ok(()));
+
+        // a mixed sequence of allocations, resizes and deallocations
+        let mut live = Vec::new();
+        for (i, size) in [8, 40, 16, 100, 24, 64, 8, 200].iter().enumerate() {
+            let layout = Layout::from_size_align(*size, ALIGN).unwrap();
+            let ptr = unsafe { tlsf.alloc(layout).unwrap() };
+            live.push((ptr, layout));
+            assert_eq!(tlsf.validate(), Ok(()));
+
+            if i % 3 == 2 {
+                let (ptr, layout) = live.remove(i / 3);
+                unsafe { tlsf.dealloc(ptr, layout) }
+                assert_eq!(tlsf.validate(), Ok(()));
+            }
+        }
+
+        let (ptr, layout) = live.remove(0);
+        let ptr = unsafe { tlsf.realloc(ptr, layout, 2 * layout.size()).unwrap() };
+        live.push((
+            ptr,
+            Layout::from_size_align(2 * layout.size(), ALIGN).unwrap(),
+        ));
+        assert_eq!(tlsf.validate(), Ok(()));
+
+        for (ptr, layout) in live {
+            unsafe { tlsf.dealloc(ptr, layout) }
+            assert_eq!(tlsf.validate(), Ok(()));
+        }
+        assert_eq!(tlsf.metrics().free_blocks, 1);
+    }
+
+    #[test]
+    fn validate_bad_size() {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        tlsf.extend(memory(512)).unwrap();
+
+        let layout = Layout::from_size_align(32, ALIGN).unwrap();
+        let ptr = unsafe { tlsf.alloc(layout).unwrap() };
+
+        // e.g. an underrun of the previous block
+        let size = 1 << 20;
+        unsafe { header(ptr).as_mut().size = size }
+        assert_eq!(
+            tlsf.validate(),
+            Err(Corruption::BadSize {
+                block: ptr.as_ptr() as usize,
+                size,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_bad_prev_link() {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        tlsf.extend(memory(512)).unwrap();
+
+        let layout = Layout::from_size_align(32, ALIGN).unwrap();
+        let (_a, b) = unsafe { (tlsf.alloc(layout).unwrap(), tlsf.alloc(layout).unwrap()) };
+
+        unsafe { header(b).as_mut().prev_phys = None }
+        assert_eq!(
+            tlsf.validate(),
+            Err(Corruption::BadPrevLink {
+                block: b.as_ptr() as usize,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_bad_bitmap() {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        tlsf.extend(memory(512)).unwrap();
+
+        // the first-level bitmap no longer says where the free block is
+        let fl = (0..4).find(|fl| tlsf.sl_bitmaps[*fl] != 0).unwrap();
+        tlsf.fl_bitmap = 0;
+        assert_eq!(tlsf.validate(), Err(Corruption::BadBitmap { fl }));
+
+        // a second-level bit set for an empty free list
+        tlsf.fl_bitmap = 1 << fl;
+        let sl = (0..8)
+            .find(|sl| tlsf.sl_bitmaps[fl] & (1 << sl) == 0)
+            .unwrap();
+        tlsf.sl_bitmaps[fl] |= 1 << sl;
+        assert_eq!(tlsf.validate(), Err(Corruption::BadBitmap { fl }));
+    }
+
+    #[test]
+    fn regions() {
+        // two regions with a gap between them
+        let buffer = memory(1024);
+        let (first, rest) = buffer.split_at_mut(256);
+        let second = &mut rest[256..];
+        let ranges = [
+            (RegionId(0), first.as_ptr() as usize, first.len()),
+            (RegionId(7), second.as_ptr() as usize, second.len()),
+        ];
+
+        let mut tlsf = Tlsf::<4, 8>::new();
+        assert_eq!(tlsf.extend(first), Ok(RegionId(0)));
+        assert_eq!(tlsf.add_region(RegionId(7), second), Ok(()));
+        assert_eq!(
+            tlsf.add_region(RegionId(7), memory(256)),
+            Err(RegionError::DuplicateId(RegionId(7)))
+        );
+        assert_eq!(tlsf.validate(), Ok(()));
+
+        // there's more than 600 bytes of free memory but not in a single region
+        assert!(tlsf.metrics().free > 600);
+        let big = Layout::from_size_align(600, ALIGN).unwrap();
+        assert!(unsafe { tlsf.alloc(big) }.is_err());
+
+        // no block crosses the boundaries of its region
+        let layout = Layout::from_size_align(32, ALIGN).unwrap();
+        let mut blocks = Vec::new();
+        while let Ok(Excess(ptr, size)) = unsafe { tlsf.alloc_excess(layout) } {
+            let addr = ptr.as_ptr() as usize;
+            let (id, start, len) = ranges
+                .iter()
+                .find(|(_, start, len)| addr >= *start && addr < start + len)
+                .unwrap();
+            assert!(addr + size <= start + len);
+            assert_eq!(tlsf.region_of(ptr), Some(*id));
+
+            blocks.push(ptr);
+        }
+        assert!(blocks
+            .iter()
+            .any(|ptr| tlsf.region_of(*ptr) == Some(RegionId(0))));
+        assert!(blocks
+            .iter()
+            .any(|ptr| tlsf.region_of(*ptr) == Some(RegionId(7))));
+        assert!(tlsf
+            .blocks()
+            .all(|block| block.region == RegionId(0) || block.region == RegionId(7)));
+
+        // the gap doesn't belong to the heap
+        let gap = ranges[0].1 + ranges[0].2 + 128;
+        assert_eq!(tlsf.region_of(NonNull::new(gap as *mut u8).unwrap()), None);
+
+        assert_eq!(
+            tlsf.retire(RegionId(0)),
+            Err(RegionError::InUse(RegionId(0)))
+        );
+        for ptr in blocks {
+            unsafe { tlsf.dealloc(ptr, layout) }
+        }
+
+        // an empty region can be retired; its memory is handed back and no longer used
+        let total = tlsf.metrics().free;
+        let retired = tlsf.retire(RegionId(0)).unwrap();
+        let addr = retired.as_ptr() as usize;
+        assert!(addr >= ranges[0].1 && addr + retired.len() <= ranges[0].1 + ranges[0].2);
+        assert!(tlsf.metrics().free < total);
+        assert_eq!(
+            tlsf.region_of(NonNull::new(retired.as_mut_ptr()).unwrap()),
+            None
+        );
+        assert!(tlsf.blocks().all(|block| block.region == RegionId(7)));
+        assert_eq!(tlsf.validate(), Ok(()));
+        assert_eq!(
+            tlsf.retire(RegionId(0)),
+            Err(RegionError::UnknownId(RegionId(0)))
+        );
+
+        // `extend` picks the ID that follows the largest one
+        assert_eq!(tlsf.extend(retired), Ok(RegionId(8)));
+    }
+
+    #[test]
+    fn extend() {
+        let mut tlsf = Tlsf::<4, 8>::new();
+        assert_eq!(tlsf.extend(memory(8)), Err(RegionError::TooSmall));
+
+        tlsf.add_region(RegionId(u32::MAX), memory(256)).unwrap();
+        assert_eq!(tlsf.extend(memory(256)), Err(RegionError::NoIdLeft));
+        assert_eq!(tlsf.add_region(RegionId(0), memory(256)), Ok(()));
     }
 }